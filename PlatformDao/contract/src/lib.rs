@@ -1,8 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::ext_contract;
-use near_sdk::collections::UnorderedMap;
-use near_sdk::{env, near_bindgen, Gas};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::{env, log, near_bindgen, AccountId, Gas, PromiseError};
 use serde::{Serialize,Deserialize};
+use lightency_governance::{Delegations, Duration, Proposal, Tally, VoteChoice};
+use lightency_errors::{require, ContractError};
 
 //Organization structure
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
@@ -11,164 +13,94 @@ pub struct Organisations{
     pub id:u32,
     pub name:String,
     pub number_members:u32,
-    
-}
-
-impl Organisations {
-    pub fn new()-> Self{
-        Self {
-            id:0, 
-            name:String::new() , 
-            number_members: 1,
-             }
-    }
-    
+    // Dissolved organisations keep their name and history
+    pub dissolved:bool,
 }
 
 //external contracts
 #[ext_contract(ext_ft)]
 pub trait OrganisationDAO {
-    fn add_dao(&mut self,dao_name:String,dao_purpose:String,duration_days:u64,duration_hours:u64,duration_min:u64);
-    fn delete(&mut self,dao_name:String);
+    fn add_dao(&mut self,dao_name:String,dao_purpose:String,founder:String,duration_days:u64,duration_hours:u64,duration_min:u64);
+    fn dissolve(&mut self,dao_name:String);
+    fn set_issuer(&mut self,dao_name:String,issuer:Option<String>);
     
 }
 
 
-//Vote structure
+// Organisation registration or deletion requested on the platform
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
 #[derive(Serialize,Deserialize)]
- pub struct Vote{
-    pub address: String,
-    pub vote:u8,
-    pub time_of_vote:u64,
- }
-
-
-// Vote implementation 
-impl Vote {
-    pub fn new() -> Self{
-        Self {
-            address: String::new(),
-            vote:0,
-            time_of_vote:0,
-        }
-    }
- }
-
+pub struct OrganisationRequest{
+    pub proposal_type:u16,
+    pub dao_name:String,
+    pub dao_purpose:String,
+}
 
- // Proposal structor 
+// Platform parameters, set at init and then only changed by passed proposals
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
 #[derive(Serialize,Deserialize)]
-pub struct Proposals{
-    pub proposal_name: String,
-    pub description: String,
-    pub proposal_creator: String,
-    pub proposal_type:u16,
+pub struct PlatformParameters{
     pub dao_name:String,
     pub dao_purpose:String,
-    pub votes_for: u32,
-    pub votes_against: u32,
-    pub time_of_creation:u64,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64,
-    pub list_voters:Vec<String>,
-    pub votes:Vec<Vote>,
+    // Voting period of member and parameter proposals
+    pub duration:Duration,
+    // Shortest voting period of organisation proposals, whose requester picks the period
+    pub min_duration:Duration,
+    // Percentage of the yes, no and veto votes a proposal needs to pass
+    pub threshold:u8,
 }
 
-
-// Proposal implementation
-impl Proposals {
-    pub fn new() -> Self{
-        Self {
-            proposal_name: String::new(),
-            description: String::new(),
-            proposal_creator: String::new(),
-            dao_name:String::new(),
-            dao_purpose:String::new(),
-            proposal_type:0,
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:0,
-            duration_days:0,
-            duration_hours:0,
-            duration_min:0,
-            list_voters:Vec::new(),
-            votes:Vec::new(),
-        }
-    }
-
-    pub fn create_vote(&mut self, vote:u8) -> Self{
-        for i in self.list_voters.clone(){
-            assert!(
-                env::signer_account_id().to_string() != i,
-                "You already voted"
-            );
-        }
-        let v = Vote{
-            address: env::signer_account_id().to_string(),
-            vote:vote,
-            time_of_vote:env::block_timestamp(),
-        };
-        self.votes.push(v);
-        if vote==0 {
-            self.votes_against=self.votes_against+1;
-        }else{
-            self.votes_for=self.votes_for+1;
-        }
-        self.list_voters.push(env::signer_account_id().to_string());
-        Self { 
-            proposal_name: self.proposal_name.clone(), 
-            description: self.description.clone(),
-            proposal_creator: self.proposal_creator.clone(),
-            proposal_type:self.proposal_type.clone(),
-            dao_name:self.dao_name.clone(),
-            dao_purpose:self.dao_purpose.clone(),
-            votes_for: self.votes_for, 
-            votes_against: self.votes_against, 
-            time_of_creation: self.time_of_creation,
-            duration_days: self.duration_days, 
-            duration_hours: self.duration_hours, 
-            duration_min: self.duration_min, 
-            list_voters: self.list_voters.clone(),
-            votes: self.votes.clone() 
-        }
-    }
-
-    pub fn end_time(&self) -> u64 {
-        self.time_of_creation+(self.duration_days*86400000000+self.duration_hours*3600000000+self.duration_min*60000000)
-    }
-
-    pub fn check_proposal(&self)->bool{
-        if (env::block_timestamp() > self.end_time()) && (self.votes_for > self.votes_against){
-            return true;
-        }
-        return false;
+impl PlatformParameters {
+    pub fn assert_valid(&self) -> Result<(), ContractError> {
+        require(self.threshold <= 100, ContractError::InvalidArgument("threshold is a percentage"))?;
+        require(self.min_duration.as_nanos() > 0, ContractError::InvalidArgument("voting duration can't be zero"))?;
+        require(
+            self.duration.as_nanos() >= self.min_duration.as_nanos(),
+            ContractError::InvalidArgument("voting duration is shorter than the minimum"),
+        )
     }
+}
 
+// Action of a platform proposal
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+#[derive(Serialize,Deserialize)]
+pub enum PlatformAction{
+    Organisation(OrganisationRequest),
+    AddMember{ account:String },
+    RemoveMember{ account:String },
+    UpdateParameters(PlatformParameters),
+    // Certify an account as energy certificate issuer
+    RegisterIssuer{ account:String },
+    // Revoke a certified issuer, once it is assigned to no organisation
+    RevokeIssuer{ account:String },
+    AssignIssuer{ organisation_id:u32, issuer:String },
+    UnassignIssuer{ organisation_id:u32 },
 }
 
+// Platform proposal, votes and tally live in the shared governance crate
+pub type Proposals = Proposal<PlatformAction>;
+
 // Define the PlatformDao PlatformDao structure
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct PlatformDao {
-    pub dao_name: String,
-    pub dao_purpose: String,
     pub founder: String,
     pub dao_members: Vec<String>,
-    pub assignedIssuers:UnorderedMap<u16,u16>,
-    pub numb_members: u64,
-    //Organisations
-    pub numb_of_organisations:u32,
-    pub organisations:Vec<Organisations>,
+    // Organisation dao contract, forwards dao requests of its users
+    pub organisation_dao: AccountId,
+    //Organisations, keyed by an id that is never reused
+    pub next_organisation_id:u32,
+    pub organisations:UnorderedMap<u32,Organisations>,
+    pub organisation_ids:LookupMap<String,u32>,
+    //Issuers, certified accounts and the one assigned to each organisation
+    pub issuers:UnorderedSet<String>,
+    pub assigned_issuers:UnorderedMap<u32,String>,
     //proposal
     pub number_of_proposals:u16,
     pub proposals: Vec<Proposals>,
     //Voting
-    pub threshold:u8,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64
+    pub delegations: Delegations,
+    pub parameters: PlatformParameters,
 }
 
 // Define the default, which automatically initializes the PlatformDao
@@ -179,46 +111,96 @@ impl Default for PlatformDao{
 }
 
 // Make sure that the caller of the function is the owner
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
+}
+
+// Make sure that the caller of the function is a platform member
+fn assert_member(platform: &PlatformDao) -> Result<String, ContractError> {
+    let account = env::predecessor_account_id().to_string();
+    require(platform.dao_members.contains(&account), ContractError::Unauthorized("platform members"))?;
+    Ok(account)
+}
+
+// Make sure that the caller of the function is the organisation dao contract
+fn assert_organisation(platform: &PlatformDao) -> Result<(), ContractError> {
+    require(
+        env::predecessor_account_id() == platform.organisation_dao,
+        ContractError::Unauthorized("organisation dao contract"),
+    )
+}
+
+// Internal helpers, not exposed as contract methods
+impl PlatformDao {
+    // Replace a proposal whith a new one
+    fn replace_proposal(&mut self, proposal: Proposals) -> Result<(), ContractError> {
+        let index = self.proposals
+            .iter()
+            .position(|p| p.proposal_name == proposal.proposal_name)
+            .ok_or(ContractError::NotFound("proposal"))?;
+        self.proposals[index] = proposal;
+        Ok(())
+    }
 }
 
 // Implement the PlatformDao structure
 #[near_bindgen]
 impl PlatformDao {
+    // The founder and the initial council are the first members
     #[init]
-    pub fn new() -> Self{
-        Self {
-            dao_name:String::new(),
-            dao_purpose:String::new(),
-            founder:String::new(),
-            dao_members:Vec::new(),
-            assignedIssuers:UnorderedMap::new(b"m"),
-            organisations:Vec::new(),
-            numb_of_organisations:0,
-            numb_members:0,
+    #[handle_result]
+    pub fn new(founder: String, council: Vec<String>, parameters: PlatformParameters, organisation_dao: AccountId) -> Result<Self, ContractError> {
+        parameters.assert_valid()?;
+        let mut dao_members = vec![founder.clone()];
+        for account in council {
+            if !dao_members.contains(&account) {
+                dao_members.push(account);
+            }
+        }
+        Ok(Self {
+            founder,
+            dao_members,
+            organisation_dao,
+            next_organisation_id:0,
+            organisations:UnorderedMap::new(b"o"),
+            organisation_ids:LookupMap::new(b"i"),
+            issuers:UnorderedSet::new(b"c"),
+            assigned_issuers:UnorderedMap::new(b"a"),
             number_of_proposals:0,
             proposals:Vec::new(),
-            threshold:0,
-            duration_days:0,
-            duration_hours:0,
-            duration_min:0,
-        }
+            delegations:Delegations::new(),
+            parameters,
+        })
+    }
+
+    // Get all platform members
+    pub fn get_members(&self) -> Vec<String>{
+        self.dao_members.clone()
+    }
+
+    pub fn get_number_members(&self) -> u64{
+        self.dao_members.len() as u64
+    }
+
+    pub fn get_parameters(&self) -> PlatformParameters{
+        self.parameters.clone()
     }
     // Delete all proposals
-    pub fn delete_all_proposals(&mut self){
-        assert_self();
+    #[handle_result]
+    pub fn delete_all_proposals(&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         for i in 0..self.proposals.len() {
             self.proposals.pop();
         }
+        Ok(())
     }
 
     //Proposal type =0 (register organisation)
-    //Proposal type =1 (delete organisation)
+    //Proposal type =1 (dissolve organisation)
+    #[handle_result]
     pub fn create_proposal (
         &mut self,
         proposal_type:u16,
@@ -229,113 +211,474 @@ impl PlatformDao {
         duration_days:u64,
         duration_hours:u64,
         duration_min:u64
-    ){
-        let proposal=Proposals{
-            proposal_type:proposal_type,
-            proposal_name: proposal_name,
-            description: description,
-            dao_name:dao_name,
-            dao_purpose:dao_purpose,
-            proposal_creator: env::signer_account_id().to_string(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:env::block_timestamp(),
-            duration_days:duration_days,
-            duration_hours:duration_hours,
-            duration_min:duration_min,
-            list_voters:Vec::new(),
-            votes:Vec::new()
-        };
-        self.proposals.push(proposal);
+    ) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        self.internal_create_proposal(
+            proposer,
+            proposal_name,
+            description,
+            PlatformAction::Organisation(OrganisationRequest{ proposal_type, dao_name, dao_purpose }),
+            Duration::new(duration_days, duration_hours, duration_min),
+        )
     }
 
-    pub fn replace_proposal(&mut self, proposal: Proposals){
-        let mut index =0;
-        for i in 0..self.proposals.len(){
-            match self.proposals.get(i){
-                Some(p) => if p.proposal_name==proposal.proposal_name {
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        self.proposals.swap_remove(index);
-        self.proposals.insert(index, proposal);
+    // Propose a new platform member
+    #[handle_result]
+    pub fn create_add_member_proposal(&mut self, proposal_name: String, description: String, account: String) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(!self.dao_members.contains(&account), ContractError::AlreadyExists("member"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::AddMember{ account }, self.parameters.duration)
+    }
+
+    // Propose to remove a platform member
+    #[handle_result]
+    pub fn create_remove_member_proposal(&mut self, proposal_name: String, description: String, account: String) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(self.dao_members.contains(&account), ContractError::NotFound("member"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::RemoveMember{ account }, self.parameters.duration)
+    }
+
+    // Propose new platform parameters
+    #[handle_result]
+    pub fn create_parameters_proposal(&mut self, proposal_name: String, description: String, parameters: PlatformParameters) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        parameters.assert_valid()?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::UpdateParameters(parameters), self.parameters.duration)
+    }
+
+    // Proposal requested by a user through the organisation dao contract
+    #[handle_result]
+    pub fn request_proposal (
+        &mut self,
+        requester:String,
+        proposal_type:u16,
+        proposal_name: String,
+        description: String,
+        dao_name:String,
+        dao_purpose:String,
+        duration_days:u64,
+        duration_hours:u64,
+        duration_min:u64
+    ) -> Result<(), ContractError> {
+        assert_organisation(self)?;
+        self.internal_create_proposal(
+            requester,
+            proposal_name,
+            description,
+            PlatformAction::Organisation(OrganisationRequest{ proposal_type, dao_name, dao_purpose }),
+            Duration::new(duration_days, duration_hours, duration_min),
+        )
+    }
+
+    // Propose to certify an issuer
+    #[handle_result]
+    pub fn create_register_issuer_proposal(&mut self, proposal_name: String, description: String, account: String) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(!self.issuers.contains(&account), ContractError::AlreadyExists("issuer"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::RegisterIssuer{ account }, self.parameters.duration)
+    }
+
+    // Propose to revoke a certified issuer
+    #[handle_result]
+    pub fn create_revoke_issuer_proposal(&mut self, proposal_name: String, description: String, account: String) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(self.issuers.contains(&account), ContractError::NotFound("issuer"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::RevokeIssuer{ account }, self.parameters.duration)
+    }
+
+    // Propose to assign a certified issuer to an organisation, replacing its current issuer
+    #[handle_result]
+    pub fn create_assign_issuer_proposal(&mut self, proposal_name: String, description: String, organisation_id: u32, issuer: String) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(!self.get_organisation(organisation_id)?.dissolved, ContractError::DaoDissolved)?;
+        require(self.issuers.contains(&issuer), ContractError::NotFound("issuer"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::AssignIssuer{ organisation_id, issuer }, self.parameters.duration)
+    }
+
+    // Propose to remove the issuer of an organisation
+    #[handle_result]
+    pub fn create_unassign_issuer_proposal(&mut self, proposal_name: String, description: String, organisation_id: u32) -> Result<(), ContractError> {
+        let proposer = assert_member(self)?;
+        require(self.assigned_issuers.get(&organisation_id).is_some(), ContractError::NotFound("issuer of the organisation"))?;
+        self.internal_create_proposal(proposer, proposal_name, description, PlatformAction::UnassignIssuer{ organisation_id }, self.parameters.duration)
+    }
+
+    fn internal_create_proposal(&mut self, proposer: String, proposal_name: String, description: String, action: PlatformAction, duration: Duration) -> Result<(), ContractError> {
+        require(
+            duration.as_nanos() >= self.parameters.min_duration.as_nanos(),
+            ContractError::InvalidArgument("voting duration is shorter than the minimum"),
+        )?;
+        require(
+            self.proposals.iter().all(|p| p.proposal_name != proposal_name),
+            ContractError::AlreadyExists("proposal"),
+        )?;
+        let proposal=Proposals::new(
+            proposal_name,
+            description,
+            proposer,
+            action,
+            env::block_timestamp(),
+            duration,
+        );
+        self.proposals.push(proposal);
+        Ok(())
     }
 
     pub fn get_proposals(&self) -> Vec<Proposals>{
         self.proposals.clone()
     }
 
-    pub fn get_specific_proposal(&self, proposal_name: String) -> Proposals{
-        let mut proposal= Proposals::new();
-        for i in 0..self.proposals.len() {
-            match self.proposals.get(i){
-      
-                Some(p) => if p.proposal_name==proposal_name {
-                    proposal=p.clone();
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        proposal
+    #[handle_result]
+    pub fn get_specific_proposal(&self, proposal_name: String) -> Result<Proposals, ContractError> {
+        self.proposals
+            .iter()
+            .find(|p| p.proposal_name == proposal_name)
+            .cloned()
+            .ok_or(ContractError::NotFound("proposal"))
+    }
+
+    #[handle_result]
+    pub fn add_vote (&mut self, proposal_name: String , vote:VoteChoice) -> Result<(), ContractError> {
+        let voter = assert_member(self)?;
+        let mut proposal = self.get_specific_proposal(proposal_name)?;
+        proposal.create_vote(voter, vote, env::block_timestamp())?;
+        self.replace_proposal(proposal)
+    }
+
+    // Get the tally of a proposal among current members, delegated votes included
+    #[handle_result]
+    pub fn get_tally(&self, proposal_name: String) -> Result<Tally, ContractError> {
+        let proposal = self.get_specific_proposal(proposal_name)?;
+        Ok(proposal.tally_among(&self.delegations, |account| self.is_member(account)))
     }
 
-    pub fn add_vote (&mut self, proposal_name: String , vote:u8){
-        let mut proposal = self.get_specific_proposal(proposal_name);
-        proposal.create_vote(vote);
-        self.replace_proposal(proposal);
+    // Check if the voting period is over and the proposal reached the platform threshold
+    // Votes of accounts that are no longer members don't count
+    #[handle_result]
+    pub fn is_passed(&self, proposal_name: String) -> Result<bool, ContractError> {
+        let proposal = self.get_specific_proposal(proposal_name)?;
+        let eligible = |account: &str| self.is_member(account);
+        Ok(proposal.check_proposal_among(env::block_timestamp(), &self.delegations, eligible)
+            && proposal.tally_among(&self.delegations, eligible).reaches(self.parameters.threshold))
+    }
+
+    // Delegate your vote to another member
+    #[handle_result]
+    pub fn delegate(&mut self, to: String) -> Result<(), ContractError> {
+        let account = assert_member(self)?;
+        require(self.dao_members.contains(&to), ContractError::InvalidArgument("can only delegate to a platform member"))?;
+        self.delegations.delegate(account, to)
+    }
+
+    // Take back your delegated vote
+    #[handle_result]
+    pub fn undelegate(&mut self) -> Result<(), ContractError> {
+        self.delegations.undelegate(env::predecessor_account_id().as_ref())?;
+        Ok(())
+    }
+
+    // Get all delegations as (delegator, representative)
+    pub fn get_delegations(&self) -> Vec<(String, String)>{
+        self.delegations.list()
+    }
+
+    // Get the representative of a member
+    pub fn get_delegate_of(&self, account: String) -> Option<String>{
+        self.delegations.delegate_of(&account)
+    }
+
+    // Get the members delegating to an account
+    pub fn get_delegators_of(&self, account: String) -> Vec<String>{
+        self.delegations.delegators_of(&account)
     }
 
     pub fn get_organisations(&self) -> Vec<Organisations>{
-        self.organisations.clone()
-    }
-
-    pub fn get_specific_organisation(&self, organisation_name: String) -> Organisations{
-        let mut organisation= Organisations::new();
-        for i in 0..self.organisations.len() {
-            match self.organisations.get(i){
-      
-                Some(o) => if o.name==organisation_name{
-                    organisation=o.clone();
-                },
-                None => panic!("There is no DAOs"),
+        self.organisations.values().collect()
+    }
+
+    #[handle_result]
+    pub fn get_organisation(&self, id: u32) -> Result<Organisations, ContractError> {
+        self.organisations.get(&id).ok_or(ContractError::NotFound("organisation"))
+    }
+
+    #[handle_result]
+    pub fn get_specific_organisation(&self, organisation_name: String) -> Result<Organisations, ContractError> {
+        let id = self.organisation_ids.get(&organisation_name).ok_or(ContractError::NotFound("organisation"))?;
+        self.get_organisation(id)
+    }
+
+    // Execute a passed proposal, once. Organisation requests complete in a callback
+    #[handle_result]
+    pub fn process_proposal(&mut self, proposal_name:String) -> Result<(), ContractError> {
+        require(self.is_passed(proposal_name.clone())?, ContractError::NotApproved)?;
+        let mut proposal = self.get_specific_proposal(proposal_name)?;
+        proposal.start_execution()?;
+        self.replace_proposal(proposal.clone())?;
+
+        match proposal.kind {
+            PlatformAction::Organisation(request) => {
+                return self.process_organisation_request(proposal.proposal_name, request, proposal.proposal_creator, proposal.duration);
+            }
+            PlatformAction::AddMember{ account } => {
+                require(!self.dao_members.contains(&account), ContractError::AlreadyExists("member"))?;
+                self.dao_members.push(account);
+            }
+            PlatformAction::RemoveMember{ account } => {
+                require(self.dao_members.len() > 1, ContractError::InvalidState("the platform can't lose its last member"))?;
+                let index = self.dao_members.iter().position(|m| *m == account).ok_or(ContractError::NotFound("member"))?;
+                self.dao_members.swap_remove(index);
+                self.delegations.remove_member(&account);
+            }
+            PlatformAction::UpdateParameters(parameters) => self.parameters = parameters,
+            PlatformAction::RegisterIssuer{ account } => {
+                require(self.issuers.insert(&account), ContractError::AlreadyExists("issuer"))?;
+            }
+            PlatformAction::RevokeIssuer{ account } => {
+                require(
+                    self.assigned_issuers.values().all(|issuer| issuer != account),
+                    ContractError::InvalidState("unassign the issuer from its organisations first"),
+                )?;
+                require(self.issuers.remove(&account), ContractError::NotFound("issuer"))?;
+            }
+            PlatformAction::AssignIssuer{ organisation_id, issuer } => {
+                require(self.issuers.contains(&issuer), ContractError::NotFound("issuer"))?;
+                require(!self.get_organisation(organisation_id)?.dissolved, ContractError::DaoDissolved)?;
+                return self.sync_issuer(proposal.proposal_name, organisation_id, Some(issuer));
+            }
+            PlatformAction::UnassignIssuer{ organisation_id } => {
+                return self.sync_issuer(proposal.proposal_name, organisation_id, None);
             }
         }
-        organisation
-    } 
-
-    pub fn process_proposal(&mut self, proposal_name:String){
-        let proposal = &self.get_specific_proposal(proposal_name);
-        let contract="organisationdao.testnet".to_string().try_into().unwrap();
-        if proposal.check_proposal() == true{
-            if proposal.proposal_type==0{
-                let current_numb=self.numb_of_organisations;
-                let new_org= Organisations{
-                    id:current_numb,
-                    name:proposal.dao_name.clone(), 
-                    number_members: 1
-                };
-                self.organisations.push(new_org);
-                self.numb_of_organisations=current_numb+1;
-                ext_ft::ext(contract)
+        self.finish_execution(proposal.proposal_name, true)
+    }
+
+    fn process_organisation_request(&mut self, proposal_name: String, request: OrganisationRequest, requester: String, duration: Duration) -> Result<(), ContractError> {
+        let contract=self.organisation_dao.clone();
+        if request.proposal_type==0{
+            // The name and id are reserved now, and released if the organisation dao fails
+            require(self.organisation_ids.get(&request.dao_name).is_none(), ContractError::AlreadyExists("organisation"))?;
+            let id=self.next_organisation_id;
+            let new_org= Organisations{
+                id,
+                name:request.dao_name.clone(), 
+                number_members: 1,
+                dissolved: false,
+            };
+            self.organisations.insert(&id, &new_org);
+            self.organisation_ids.insert(&new_org.name, &id);
+            self.next_organisation_id=id+1;
+            ext_ft::ext(contract)
+            .with_static_gas(Gas(5*1000000000000))
+            .add_dao(request.dao_name,request.dao_purpose,requester,duration.days,duration.hours,duration.minutes)
+            .then(
+                Self::ext(env::current_account_id())
                 .with_static_gas(Gas(5*1000000000000))
-                .add_dao(proposal.dao_name.clone(),proposal.dao_purpose.clone(),proposal.duration_days,proposal.duration_hours,proposal.duration_min);
-            }
-            else {
-                let org = self.get_specific_organisation(proposal.dao_name.clone());
-                self.organisations.swap_remove(org.id.try_into().unwrap());
-                ext_ft::ext(contract)
+                .on_organisation_added(proposal_name, id)
+            );
+        }
+        else {
+            // The organisation is marked dissolved once the organisation dao dissolved it
+            let org = self.get_specific_organisation(request.dao_name.clone())?;
+            require(!org.dissolved, ContractError::DaoDissolved)?;
+            ext_ft::ext(contract)
+            .with_static_gas(Gas(5*1000000000000))
+            .dissolve(request.dao_name)
+            .then(
+                Self::ext(env::current_account_id())
                 .with_static_gas(Gas(5*1000000000000))
-                .delete(proposal.dao_name.clone());
-            }
+                .on_organisation_dissolved(proposal_name, org.id)
+            );
+        }
+        Ok(())
+    }
+
+    #[private]
+    #[handle_result]
+    pub fn on_organisation_added(&mut self, #[callback_result] added: Result<(), PromiseError>, proposal_name: String, id: u32) -> Result<(), ContractError> {
+        if added.is_err() {
+            log!("Organisation {} could not be added", id);
+            let org = self.get_organisation(id)?;
+            self.organisations.remove(&id);
+            self.organisation_ids.remove(&org.name);
+        }
+        self.finish_execution(proposal_name, added.is_ok())
+    }
+
+    #[private]
+    #[handle_result]
+    pub fn on_organisation_dissolved(&mut self, #[callback_result] dissolved: Result<(), PromiseError>, proposal_name: String, id: u32) -> Result<(), ContractError> {
+        if dissolved.is_ok() {
+            let mut org = self.get_organisation(id)?;
+            org.dissolved = true;
+            self.organisations.insert(&id, &org);
         }else{
-            panic!("this proposal is not validated")
-        }  
-}
-pub fn assign_issuer(&mut self,organisation_dao_Id: u16,issuer_dao_Id:u16){
-    self.assignedIssuers.insert(&organisation_dao_Id, &issuer_dao_Id);
-}
-    
+            log!("Organisation {} could not be dissolved", id);
+        }
+        self.finish_execution(proposal_name, dissolved.is_ok())
+    }
+
+    // Copy the issuer of an organisation to the organisation dao, the platform records it once copied
+    fn sync_issuer(&mut self, proposal_name: String, organisation_id: u32, issuer: Option<String>) -> Result<(), ContractError> {
+        let org = self.get_organisation(organisation_id)?;
+        let contract=self.organisation_dao.clone();
+        ext_ft::ext(contract)
+        .with_static_gas(Gas(5*1000000000000))
+        .set_issuer(org.name, issuer.clone())
+        .then(
+            Self::ext(env::current_account_id())
+            .with_static_gas(Gas(5*1000000000000))
+            .on_issuer_synced(proposal_name, organisation_id, issuer)
+        );
+        Ok(())
+    }
+
+    #[private]
+    #[handle_result]
+    pub fn on_issuer_synced(&mut self, #[callback_result] synced: Result<(), PromiseError>, proposal_name: String, organisation_id: u32, issuer: Option<String>) -> Result<(), ContractError> {
+        if synced.is_ok() {
+            match issuer {
+                Some(issuer) => self.assigned_issuers.insert(&organisation_id, &issuer),
+                None => self.assigned_issuers.remove(&organisation_id),
+            };
+        }else{
+            log!("Issuer of organisation {} could not be updated", organisation_id);
+        }
+        self.finish_execution(proposal_name, synced.is_ok())
+    }
+
+    // Get all certified issuers
+    pub fn get_issuers(&self) -> Vec<String>{
+        self.issuers.to_vec()
+    }
+
+    pub fn is_issuer(&self, account: String) -> bool{
+        self.issuers.contains(&account)
+    }
+
+    // Get the issuer assigned to an organisation
+    pub fn get_assigned_issuer(&self, organisation_id: u32) -> Option<String>{
+        self.assigned_issuers.get(&organisation_id)
+    }
+
+    // Get the organisations an issuer is assigned to
+    pub fn get_organisations_of_issuer(&self, issuer: String) -> Vec<u32>{
+        self.assigned_issuers
+            .iter()
+            .filter(|(_, assigned)| *assigned == issuer)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn finish_execution(&mut self, proposal_name: String, success: bool) -> Result<(), ContractError> {
+        let mut proposal = self.get_specific_proposal(proposal_name)?;
+        proposal.finish_execution(success)?;
+        self.replace_proposal(proposal)
+    }
+
+    fn is_member(&self, account: &str) -> bool {
+        self.dao_members.iter().any(|member| member == account)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    const HOUR: u64 = 60 * 60 * 1_000_000_000;
+
+    fn context(predecessor: &str, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("platformdao.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .block_timestamp(timestamp)
+            .build());
+    }
+
+    fn platform() -> PlatformDao {
+        context("platformdao.testnet", 0);
+        let parameters = PlatformParameters {
+            dao_name: "platform".to_string(),
+            dao_purpose: "purpose".to_string(),
+            duration: Duration::new(0, 1, 0),
+            min_duration: Duration::new(0, 1, 0),
+            threshold: 50,
+        };
+        PlatformDao::new(
+            "founder.testnet".to_string(),
+            vec!["bob.testnet".to_string(), "carol.testnet".to_string()],
+            parameters,
+            "organisationdao.testnet".parse().unwrap(),
+        ).unwrap()
+    }
+
+    #[test]
+    fn organisation_proposals_respect_the_minimum_duration() {
+        let mut platform = platform();
+        context("founder.testnet", 0);
+        assert_eq!(
+            platform.create_proposal(0, "solar".to_string(), "".to_string(), "solar".to_string(), "".to_string(), 0, 0, 1).unwrap_err(),
+            ContractError::InvalidArgument("voting duration is shorter than the minimum")
+        );
+        platform.create_proposal(0, "solar".to_string(), "".to_string(), "solar".to_string(), "".to_string(), 0, 1, 0).unwrap();
+    }
+
+    #[test]
+    fn votes_of_removed_members_do_not_count() {
+        let mut platform = platform();
+        context("founder.testnet", 0);
+        platform.create_add_member_proposal("add-dave".to_string(), "".to_string(), "dave.testnet".to_string()).unwrap();
+        platform.create_remove_member_proposal("remove-bob".to_string(), "".to_string(), "bob.testnet".to_string()).unwrap();
+        context("bob.testnet", 0);
+        platform.add_vote("add-dave".to_string(), VoteChoice::Yes).unwrap();
+        for voter in ["founder.testnet", "carol.testnet"] {
+            context(voter, 0);
+            platform.add_vote("remove-bob".to_string(), VoteChoice::Yes).unwrap();
+        }
+
+        context("founder.testnet", 2 * HOUR);
+        assert_eq!(platform.is_passed("add-dave".to_string()), Ok(true));
+        platform.process_proposal("remove-bob".to_string()).unwrap();
+        assert_eq!(platform.is_passed("add-dave".to_string()), Ok(false));
+        assert_eq!(platform.process_proposal("add-dave".to_string()).unwrap_err(), ContractError::NotApproved);
+    }
+
+    #[test]
+    fn invalid_calls_return_stable_errors() {
+        let mut platform = platform();
+        context("outsider.testnet", 0);
+        let error = platform.create_add_member_proposal("add-dave".to_string(), "".to_string(), "dave.testnet".to_string()).unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("platform members"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+        assert_eq!(
+            platform.request_proposal("outsider.testnet".to_string(), 0, "solar".to_string(), "".to_string(), "solar".to_string(), "".to_string(), 0, 1, 0).unwrap_err(),
+            ContractError::Unauthorized("organisation dao contract")
+        );
+        context("organisationdao.testnet", 0);
+        platform.request_proposal("outsider.testnet".to_string(), 0, "solar".to_string(), "".to_string(), "solar".to_string(), "".to_string(), 0, 1, 0).unwrap();
+        context("outsider.testnet", 0);
+        assert_eq!(platform.delete_all_proposals().unwrap_err().code(), "UNAUTHORIZED");
+
+        context("founder.testnet", 0);
+        assert_eq!(platform.get_specific_proposal("add-dave".to_string()).unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(platform.get_organisation(0).unwrap_err(), ContractError::NotFound("organisation"));
+        assert_eq!(
+            platform.create_add_member_proposal("add-bob".to_string(), "".to_string(), "bob.testnet".to_string()).unwrap_err(),
+            ContractError::AlreadyExists("member")
+        );
+        platform.create_add_member_proposal("add-dave".to_string(), "".to_string(), "dave.testnet".to_string()).unwrap();
+        assert_eq!(
+            platform.create_add_member_proposal("add-dave".to_string(), "".to_string(), "erin.testnet".to_string()).unwrap_err(),
+            ContractError::AlreadyExists("proposal")
+        );
+        platform.add_vote("add-dave".to_string(), VoteChoice::Yes).unwrap();
+        assert_eq!(platform.process_proposal("add-dave".to_string()).unwrap_err().code(), "NOT_APPROVED");
+        context("founder.testnet", 2 * HOUR);
+        assert_eq!(platform.add_vote("add-dave".to_string(), VoteChoice::No).unwrap_err(), ContractError::VotingClosed);
+        assert_eq!(
+            platform.delegate("dave.testnet".to_string()).unwrap_err(),
+            ContractError::InvalidArgument("can only delegate to a platform member")
+        );
+    }
+}
@@ -1,125 +1,21 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use serde::{Serialize , Deserialize};
 use near_sdk::{env, near_bindgen, Promise, AccountId};
-
-
-// VOTE
-// Vote struct
-#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
-#[derive(Serialize, Deserialize)]
- pub struct Vote{
-    pub address: String,
-    pub vote:u8,
-    pub time_of_vote:u64,
- }
-
-// Vote implementation
- impl Vote {
-    pub fn new() -> Self{
-        Self {
-            address: String::new(),
-            vote:0,
-            time_of_vote:0,
-        }
-    }
- }
+use lightency_governance::{Delegations, Duration, Proposal, Tally, VoteChoice};
+use lightency_errors::{require, ContractError};
 
 
 // PROPOSALS
-// Proposals struct
+// Action of a proposal, pays `amount` NEAR to the benificiary once accepted
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
 #[derive(Serialize, Deserialize)]
-pub struct Proposals{
-    pub title: String,
-    pub description: String,
-    pub proposal_creator: String,
+pub struct Payout {
     pub amount: u128,
     pub benificiary: String,
-    pub votes_for: u32,
-    pub votes_against: u32,
-    pub time_of_creation:u64,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64,
-    pub list_voters:Vec<String>,
-    pub votes:Vec<Vote>,
 }
 
-// Proposals implementation 
-impl Proposals {
-    pub fn new() -> Self{
-        Self {
-            title: String::new(),
-            description: String::new(),
-            proposal_creator: String::new(),
-            amount: 0,
-            benificiary: String::new(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:0,
-            duration_days:0,
-            duration_hours:0,
-            duration_min:0,
-            list_voters: Vec::new(),
-            votes:Vec::new(),
-        }
-    }
-
-    pub fn create_vote(&mut self, vote:u8) -> Self{
-        for i in self.list_voters.clone(){
-            assert!(
-                env::signer_account_id().to_string() != i,
-                "You already voted"
-            );
-        }
-        let v = Vote{
-            address: env::signer_account_id().to_string(),
-            vote:vote,
-            time_of_vote:env::block_timestamp(),
-        };
-        self.votes.push(v);
-        if vote==0 {
-            self.votes_against=self.votes_against+1;
-        }else{
-            self.votes_for=self.votes_for+1;
-        }
-        self.list_voters.push(env::signer_account_id().to_string());
-        Self { 
-            title: self.title.clone(), 
-            description: self.description.clone(),
-            proposal_creator: self.proposal_creator.clone(), 
-            amount: self.amount, 
-            benificiary: self.benificiary.clone(), 
-            votes_for: self.votes_for, 
-            votes_against: self.votes_against, 
-            time_of_creation: self.time_of_creation, 
-            duration_days: self.duration_days, 
-            duration_hours: self.duration_hours, 
-            duration_min: self.duration_min,
-            list_voters: self.list_voters.clone(), 
-            votes: self.votes.clone() 
-        }
-    }
-
-    pub fn get_amount(&self) -> u128 {
-        self.amount
-    }
-
-    pub fn get_benificiary(&self) -> String {
-        self.benificiary.clone()
-    }
-
-    pub fn end_time(&self) -> u64 {
-        self.time_of_creation+(self.duration_days*86400000000+self.duration_hours*3600000000+self.duration_min*60000000)
-    }
-
-    pub fn check_proposal(&self)->bool{
-        if env::block_timestamp() > self.end_time() {
-            return true;
-        }
-        return false;
-    }
-}
+// Pool proposal, votes and tally live in the shared governance crate
+pub type Proposals = Proposal<Payout>;
 
 
 // Define the contract structure
@@ -128,6 +24,7 @@ impl Proposals {
 pub struct EnergiePoolContract {
     members: Vec<String>,
     records: Vec<Proposals>,
+    delegations: Delegations,
 }
 
 // Define the default, which automatically initializes the contract
@@ -138,12 +35,24 @@ impl Default for EnergiePoolContract {
 }
 
 // Make sure that the caller of the function is the owner
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
+}
+
+// Internal helpers, not exposed as contract methods
+impl EnergiePoolContract {
+    // replace a proposal 
+    fn replace_proposal(&mut self, proposal: Proposals) -> Result<(), ContractError> {
+        let index = self.records
+            .iter()
+            .position(|p| p.proposal_name == proposal.proposal_name)
+            .ok_or(ContractError::NotFound("proposal"))?;
+        self.records[index] = proposal;
+        Ok(())
+    }
 }
 
 // Implement the contract structure
@@ -152,25 +61,28 @@ fn assert_self() {
 impl EnergiePoolContract {
     #[init]
     pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
         Self {
             members: Vec::new(),
             records: Vec::new(),
+            delegations: Delegations::new(),
         }
     }
 
     // delete all proposals
-    pub fn delete_all(&mut self){
-        assert_self();
+    #[handle_result]
+    pub fn delete_all(&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         for _i in 0..self.records.len(){
             self.records.pop();
         }
+        Ok(())
     }
 
     // Methods.
 
     // Add a member
-    pub fn add_member (&mut self, account:String) {
+    #[handle_result]
+    pub fn add_member (&mut self, account:String) -> Result<(), ContractError> {
         let mut existance = false;
         for i in self.members.clone(){
             if i == account {
@@ -178,8 +90,9 @@ impl EnergiePoolContract {
                 break;
             }
         }
-        assert!(existance == false , "Account already exist");
+        require(!existance, ContractError::AlreadyExists("member"))?;
         self.members.push(account);
+        Ok(())
     }
 
     // Remove a member
@@ -190,9 +103,42 @@ impl EnergiePoolContract {
                 break;
             }
         }
+        self.delegations.remove_member(&account);
+    }
+
+    // Delegate your vote to another member
+    #[handle_result]
+    pub fn delegate(&mut self, to: String) -> Result<(), ContractError> {
+        let account = env::predecessor_account_id().to_string();
+        require(self.members.contains(&account), ContractError::Unauthorized("pool members"))?;
+        require(self.members.contains(&to), ContractError::InvalidArgument("can only delegate to a pool member"))?;
+        self.delegations.delegate(account, to)
+    }
+
+    // Take back your delegated vote
+    #[handle_result]
+    pub fn undelegate(&mut self) -> Result<(), ContractError> {
+        self.delegations.undelegate(env::predecessor_account_id().as_ref())?;
+        Ok(())
+    }
+
+    // Get all delegations as (delegator, representative)
+    pub fn get_delegations(&self) -> Vec<(String, String)>{
+        self.delegations.list()
+    }
+
+    // Get the representative of a member
+    pub fn get_delegate_of(&self, account: String) -> Option<String>{
+        self.delegations.delegate_of(&account)
+    }
+
+    // Get the members delegating to an account
+    pub fn get_delegators_of(&self, account: String) -> Vec<String>{
+        self.delegations.delegators_of(&account)
     }
 
     // create proposal
+    #[handle_result]
     pub fn create_proposal (
         &mut self,
         title: String,
@@ -202,46 +148,25 @@ impl EnergiePoolContract {
         duration_days:u64,
         duration_hours:u64,
         duration_min:u64
-    ){  
+    ) -> Result<(), ContractError> {
         let mut existance = false;
         for i in self.members.clone(){
-            if i == env::signer_account_id().to_string() {
+            if i == env::predecessor_account_id().to_string() {
                 existance = true;
                 break;
             }
         }
-        assert!(existance == true , "You are not one of the councils");
-        let proposal=Proposals{
-            title: title,
-            description: description,
-            proposal_creator: env::signer_account_id().to_string(),
-            amount: amount,
-            benificiary: benificiary,
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:env::block_timestamp(),
-            duration_days:duration_days,
-            duration_hours:duration_hours,
-            duration_min:duration_min,
-            list_voters: Vec::new(),
-            votes:Vec::new()
-        };
+        require(existance, ContractError::Unauthorized("pool members"))?;
+        let proposal=Proposals::new(
+            title,
+            description,
+            env::predecessor_account_id().to_string(),
+            Payout { amount, benificiary },
+            env::block_timestamp(),
+            Duration::new(duration_days, duration_hours, duration_min),
+        );
         self.records.push(proposal);
-    }
-
-    // replace a proposal 
-    pub fn replace_proposal(&mut self, proposal: Proposals){
-        let mut index =0;
-        for i in 0..self.records.len(){
-            match self.records.get(i){
-                Some(p) => if p.title==proposal.title {
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        self.records.swap_remove(index);
-        self.records.insert(index, proposal);
+        Ok(())
     }
 
     // get all proposals 
@@ -250,52 +175,57 @@ impl EnergiePoolContract {
     }
 
     //get a specific proposal 
-    pub fn get_specific_proposal(&self, title: String) -> Proposals{
-        let mut proposal= Proposals::new();
-        for i in 0..self.records.len() {
-            match self.records.get(i){
-                Some(p) => if p.title==title {
-                    proposal=p.clone();
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        proposal
+    #[handle_result]
+    pub fn get_specific_proposal(&self, title: String) -> Result<Proposals, ContractError> {
+        self.records
+            .iter()
+            .find(|p| p.proposal_name == title)
+            .cloned()
+            .ok_or(ContractError::NotFound("proposal"))
     }
 
     //get the end time of a specific proposal
-    pub fn get_end_time(&self,title: String) -> u64 {
-        let proposal=self.get_specific_proposal(title);
-        proposal.end_time()
+    #[handle_result]
+    pub fn get_end_time(&self,title: String) -> Result<u64, ContractError> {
+        let proposal=self.get_specific_proposal(title)?;
+        Ok(proposal.end_time())
     }
     
     // add a vote 
+    #[handle_result]
     pub fn add_vote(
         &mut self,
         title: String,
-        vote: u8
-    ){
-        let mut proposal = self.get_specific_proposal(title);
-        proposal = proposal.create_vote(vote);
-        self.replace_proposal(proposal);
+        vote: VoteChoice
+    ) -> Result<(), ContractError> {
+        let mut proposal = self.get_specific_proposal(title)?;
+        proposal.create_vote(env::predecessor_account_id().to_string(), vote, env::block_timestamp())?;
+        self.replace_proposal(proposal)
+    }
+
+    // get the tally of a proposal, delegated votes included
+    #[handle_result]
+    pub fn get_tally(&self, title: String) -> Result<Tally, ContractError> {
+        Ok(self.get_specific_proposal(title)?.tally(&self.delegations))
     }
 
     // get votes for 
-    pub fn get_votes_for(&self, title: String) -> u32 {
-        let proposal = self.get_specific_proposal(title);   
-        proposal.votes_for
+    #[handle_result]
+    pub fn get_votes_for(&self, title: String) -> Result<u32, ContractError> {
+        Ok(self.get_tally(title)?.yes)
     }
 
-    // get votes against 
-    pub fn get_votes_against(&self, title: String) -> u32 {
-        let proposal = self.get_specific_proposal(title);   
-        proposal.votes_against
+    // get votes against, vetoes included
+    #[handle_result]
+    pub fn get_votes_against(&self, title: String) -> Result<u32, ContractError> {
+        let tally = self.get_tally(title)?;
+        Ok(tally.no + tally.veto)
     }
 
     // get number of votes 
-    pub fn get_nember_votes(&self, title: String) -> u32{
-        let proposal = self.get_specific_proposal(title);
-        proposal.votes_against + proposal.votes_for
+    #[handle_result]
+    pub fn get_nember_votes(&self, title: String) -> Result<u32, ContractError> {
+        Ok(self.get_tally(title)?.total())
     }
 
     // funtion that pay near to an account
@@ -304,19 +234,59 @@ impl EnergiePoolContract {
     }
 
     // check the proposal and send near to the benificiary if it's true
-    pub fn check_and_send_near(&self,title: String) -> String{
-        let proposal = self.get_specific_proposal(title);
-        let check= proposal.check_proposal();
+    #[handle_result]
+    pub fn check_and_send_near(&self,title: String) -> Result<String, ContractError> {
+        let proposal = self.get_specific_proposal(title)?;
+        let check= proposal.check_proposal(env::block_timestamp(), &self.delegations);
         if check==true {
-            let benificiary= proposal.get_benificiary().try_into().unwrap();
-            let amount= proposal.get_amount() * 1000000000000000000000000;
+            let benificiary= proposal.kind.benificiary.try_into()
+                .map_err(|_| ContractError::InvalidArgument("benificiary is not a valid account"))?;
+            let amount= proposal.kind.amount * 1000000000000000000000000;
             let _payment=self.pay(amount,benificiary);
             let msg="Proposal accepted and amount was sent".to_string();
-            msg
+            Ok(msg)
         }else{
             let msg="Proposal refused".to_string();
-            msg
+            Ok(msg)
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn context(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("energypool.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    #[test]
+    fn invalid_calls_return_stable_errors() {
+        context("energypool.testnet");
+        let mut pool = EnergiePoolContract::new();
+        pool.add_member("alice.testnet".to_string()).unwrap();
+        let error = pool.add_member("alice.testnet".to_string()).unwrap_err();
+        assert_eq!(error, ContractError::AlreadyExists("member"));
+        assert_eq!(error.code(), "ALREADY_EXISTS");
+
+        context("bob.testnet");
+        assert_eq!(
+            pool.create_proposal("solar".to_string(), "".to_string(), 1, "bob.testnet".to_string(), 1, 0, 0).unwrap_err(),
+            ContractError::Unauthorized("pool members")
+        );
+        assert_eq!(pool.delete_all().unwrap_err().code(), "UNAUTHORIZED");
+        assert_eq!(pool.add_vote("solar".to_string(), VoteChoice::Yes).unwrap_err(), ContractError::NotFound("proposal"));
+        assert_eq!(pool.get_votes_for("solar".to_string()).unwrap_err().code(), "NOT_FOUND");
+
+        context("alice.testnet");
+        assert_eq!(
+            pool.delegate("bob.testnet".to_string()).unwrap_err(),
+            ContractError::InvalidArgument("can only delegate to a pool member")
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{UnorderedMap};
-use near_sdk::{env, near_bindgen, ext_contract,Gas};
+use near_sdk::{env, near_bindgen, ext_contract,Gas,AccountId};
 use serde::{Serialize,Deserialize};
+use lightency_governance::{Delegations, Duration, Proposal, Tally, VoteChoice};
+use lightency_errors::{require, ContractError};
 
 pub const TGAS: u64 = 1_000_000_000_000;
 
@@ -10,123 +12,25 @@ pub trait Lts {
     fn ft_transfer (&mut self, receiver_id:String, amount:String, memo:String);
 }
 
-// VOTE
-// Vote structor 
+// Action of a council proposal
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-pub struct Vote{
-    pub address: String,
-    pub vote:u8,
-    pub time_of_vote:u64,
-}
-
-// Vote implementation 
-impl Vote {
-    // Initialise a new vote
-    pub fn new() -> Self{
-        Self {
-            address: String::new(),
-            vote:0,
-            time_of_vote:0,
-        }
-    }
-}
-
-// Proposal structor
-#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-pub struct Proposal{
+pub struct FundingRequest {
     pub proposal_type: u8,
-    pub proposal_name: String,
-    pub description: String,
     pub amount: u128,
-    pub proposal_creator: String,
-    pub votes_for: u32,
-    pub votes_against: u32,
-    pub time_of_creation:u64,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64,
-    pub list_voters:Vec<String>,
-    pub votes:Vec<Vote>,
 }
 
-impl Proposal{
-    pub fn new() -> Self{
-        Self{
-            proposal_type:0,
-            proposal_name: String::new(),
-            description: String::new(),
-            amount:0,
-            proposal_creator: String::new(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:0,
-            duration_days:0,
-            duration_hours:0,
-            duration_min:0,
-            list_voters:Vec::new(),
-            votes:Vec::new(),
-        }
-    }
-
-    // Create a new vote 
-    // Returns a propsal contains the new vote 
-    pub fn create_vote(&mut self, vote:u8) -> Self{
-        for i in self.list_voters.clone(){
-            assert!(
-                env::signer_account_id().to_string() != i,
-                "You already voted"
-            );
-        }
-        let v = Vote{
-            address: env::signer_account_id().to_string(),
-            vote:vote,
-            time_of_vote:env::block_timestamp(),
-        };
-        self.votes.push(v);
-        if vote==0 {
-            self.votes_against=self.votes_against+1;
-        }else{
-            self.votes_for=self.votes_for+1;
-        }
-        self.list_voters.push(env::signer_account_id().to_string());
-        Self { 
-            proposal_type:self.proposal_type,
-            proposal_name: self.proposal_name.clone(), 
-            description: self.description.clone(),
-            amount: self.amount,
-            proposal_creator: self.proposal_creator.clone(),
-            votes_for: self.votes_for, 
-            votes_against: self.votes_against, 
-            time_of_creation: self.time_of_creation, 
-            duration_days: self.duration_days, 
-            duration_hours: self.duration_hours, 
-            duration_min: self.duration_min, 
-            list_voters: self.list_voters.clone(),
-            votes: self.votes.clone() 
-        }
-    }
-
-    // Get the end time of a proposal 
-    pub fn end_time(&self) -> u64 {
-        self.time_of_creation+(self.duration_days*86400000000000+self.duration_hours*3600000000000+self.duration_min*60000000000)
-    }
-
-    // Check if the time of a proposal is end or not 
-    pub fn check_proposal(&self)->bool{
-        if (env::block_timestamp() > self.end_time()) && (self.votes_for > self.votes_against){
-            return true;
-        }
-        return false;
-    } 
-
-}
+// Council proposal, votes and tally live in the shared governance crate
+pub type CouncilProposal = Proposal<FundingRequest>;
 
 // Define the contract structure
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct EnergyDao {
     members: UnorderedMap<String,u8>,
-    proposals: Vec<Proposal>,
+    proposals: Vec<CouncilProposal>,
+    delegations: Delegations,
+    // LTS token contract, set at initialization
+    lts_token: AccountId,
 }
 
 // Define the default, which automatically initializes the contract
@@ -137,12 +41,24 @@ impl Default for EnergyDao {
 }
 
 // Make sure that the caller of the function is the owner
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
+}
+
+// Internal helpers, not exposed as contract methods
+impl EnergyDao {
+    // Replace a proposal whith a new one 
+    fn replace_proposal(&mut self, proposal: CouncilProposal) -> Result<(), ContractError> {
+        let index = self.proposals
+            .iter()
+            .position(|p| p.proposal_name == proposal.proposal_name)
+            .ok_or(ContractError::NotFound("proposal"))?;
+        self.proposals[index] = proposal;
+        Ok(())
+    }
 }
 
 // Implement the contract structure
@@ -150,23 +66,29 @@ fn assert_self() {
 #[near_bindgen]
 impl EnergyDao {
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(lts_token: AccountId) -> Self {
         Self {
             members : UnorderedMap::new(b"m"),
             proposals : Vec::new(),
+            delegations : Delegations::new(),
+            lts_token,
         }
     }
 
-    pub fn init(&mut self) {
-        assert_self();
+    #[handle_result]
+    pub fn init(&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         self.members.insert(&env::current_account_id().to_string(), &0);
+        Ok(())
     }
 
     // delete all members 
-    pub fn delete_all (&mut self) {
-        assert_self();
+    #[handle_result]
+    pub fn delete_all (&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         self.members.clear();
+        self.delegations = Delegations::new();
+        Ok(())
     }
 
     // get all councils
@@ -215,6 +137,7 @@ impl EnergyDao {
     }
 
     // Create a new proposal 
+    #[handle_result]
     pub fn create_proposal (
         &mut self,
         proposal_type:u8,
@@ -224,119 +147,180 @@ impl EnergyDao {
         duration_days: u64,
         duration_hours: u64,
         duration_min: u64,
-    ){
-        assert_eq!(
-            self.check_council(env::signer_account_id().to_string()),
-            true,
-            "Proposals can be created only by the councils"
+    ) -> Result<(), ContractError> {
+        require(
+            self.check_council(env::predecessor_account_id().to_string()),
+            ContractError::Unauthorized("council"),
+        )?;
+        let proposal=CouncilProposal::new(
+            proposal_name,
+            description,
+            env::predecessor_account_id().to_string(),
+            FundingRequest { proposal_type, amount },
+            env::block_timestamp(),
+            Duration::new(duration_days, duration_hours, duration_min),
         );
-        let proposal=Proposal{
-            proposal_type:proposal_type,
-            proposal_name: proposal_name,
-            description: description,
-            amount:amount,
-            proposal_creator: env::signer_account_id().to_string(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:env::block_timestamp(),
-            duration_days:duration_days,
-            duration_hours:duration_hours,
-            duration_min:duration_min,
-            list_voters:Vec::new(),
-            votes:Vec::new()
-        };
         self.proposals.push(proposal);
-    }
-
-    // Replace a proposal whith a new one 
-    pub fn replace_proposal(&mut self, proposal: Proposal){
-        let mut index =0;
-        for i in 0..self.proposals.len(){
-            match self.proposals.get(i){
-                Some(p) => if p.proposal_name==proposal.proposal_name {
-                    index=i;
-                },
-                None => panic!("There is no PROPOSALs"),
-            }
-        }
-        self.proposals.swap_remove(index);
-        self.proposals.insert(index, proposal);
+        Ok(())
     }
 
     // Get all proposals 
-    pub fn get_proposals(&self) -> Vec<Proposal>{
+    pub fn get_proposals(&self) -> Vec<CouncilProposal>{
         self.proposals.clone()
     }
 
     // Get a spsific proposal 
-    pub fn get_specific_proposal(&self, proposal_name: String) -> Proposal{
-        let mut proposal= Proposal::new();
-        for i in 0..self.proposals.len() {
-            match self.proposals.get(i){
-                Some(p) => if p.proposal_name==proposal_name {
-                    proposal=p.clone();
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        proposal
+    #[handle_result]
+    pub fn get_specific_proposal(&self, proposal_name: String) -> Result<CouncilProposal, ContractError> {
+        self.proposals
+            .iter()
+            .find(|p| p.proposal_name == proposal_name)
+            .cloned()
+            .ok_or(ContractError::NotFound("proposal"))
     }
 
     // add a vote 
+    #[handle_result]
     pub fn add_vote(
         &mut self,
         proposal_name: String,
-        vote: u8
-    ){
-        if env::block_timestamp() < self.get_specific_proposal(proposal_name.clone()).end_time() {
-            assert_eq!(
-                self.check_member(env::signer_account_id().to_string()),
-                true,
-                "You must be one of the dao members to vote"
-            );
-            let proposal =self.get_specific_proposal(proposal_name.clone()).create_vote(vote);
-            self.replace_proposal(proposal);
-        }else {
-            panic!("Proposal has been expired");
-        }
-        
+        vote: VoteChoice
+    ) -> Result<(), ContractError> {
+        require(
+            self.check_member(env::predecessor_account_id().to_string()),
+            ContractError::Unauthorized("dao members"),
+        )?;
+        let mut proposal = self.get_specific_proposal(proposal_name)?;
+        proposal.create_vote(env::predecessor_account_id().to_string(), vote, env::block_timestamp())?;
+        self.replace_proposal(proposal)
+    }
+
+    // Get the tally of a proposal, delegated votes included
+    #[handle_result]
+    pub fn get_tally(&self, proposal_name: String) -> Result<Tally, ContractError> {
+        Ok(self.get_specific_proposal(proposal_name)?.tally(&self.delegations))
+    }
+
+    // Delegate your vote to another member
+    #[handle_result]
+    pub fn delegate(&mut self, to: String) -> Result<(), ContractError> {
+        let account = env::predecessor_account_id().to_string();
+        require(self.check_member(account.clone()), ContractError::Unauthorized("dao members"))?;
+        require(self.check_member(to.clone()), ContractError::InvalidArgument("can only delegate to a dao member"))?;
+        self.delegations.delegate(account, to)
+    }
+
+    // Take back your delegated vote
+    #[handle_result]
+    pub fn undelegate(&mut self) -> Result<(), ContractError> {
+        self.delegations.undelegate(env::predecessor_account_id().as_ref())?;
+        Ok(())
+    }
+
+    // Get all delegations as (delegator, representative)
+    pub fn get_delegations(&self) -> Vec<(String, String)>{
+        self.delegations.list()
+    }
+
+    // Get the representative of a member
+    pub fn get_delegate_of(&self, account: String) -> Option<String>{
+        self.delegations.delegate_of(&account)
+    }
+
+    // Get the members delegating to an account
+    pub fn get_delegators_of(&self, account: String) -> Vec<String>{
+        self.delegations.delegators_of(&account)
     }
 
     // add a council
-    pub fn add_council(&mut self, account:String){
-        assert_eq!(
-            self.check_council(env::signer_account_id().to_string()),
-            true,
-            "To add a council you must be one of the councils"
-        );
+    #[handle_result]
+    pub fn add_council(&mut self, account:String) -> Result<(), ContractError> {
+        require(
+            self.check_council(env::predecessor_account_id().to_string()),
+            ContractError::Unauthorized("council"),
+        )?;
         self.members.insert(&account, &0);
+        Ok(())
     }
 
     // add community
-    pub fn add_community (&mut self,account:String) {
-            self.members.insert(&account, &1); 
+    #[handle_result]
+    pub fn add_community (&mut self,account:String) -> Result<(), ContractError> {
+        require(
+            self.check_council(env::predecessor_account_id().to_string()),
+            ContractError::Unauthorized("council"),
+        )?;
+        self.members.insert(&account, &1);
+        Ok(())
     }
 
     // check the proposal and return a message
-    pub fn check_the_proposal(&self,proposal_name: String) -> String{
-        let proposal=self.get_specific_proposal(proposal_name);
-        let check= proposal.check_proposal();
+    #[handle_result]
+    pub fn check_the_proposal(&self,proposal_name: String) -> Result<String, ContractError> {
+        let proposal=self.get_specific_proposal(proposal_name)?;
+        let check= proposal.check_proposal(env::block_timestamp(), &self.delegations);
         if check==true {
             let msg="Proposal accepted".to_string();
-            msg
+            Ok(msg)
         }else{
             let msg="Proposal refused".to_string();
-            msg
+            Ok(msg)
         }
     }
 
     // fund function 
-    pub fn fund (&mut self,account:String,amount:u128){
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+    #[handle_result]
+    pub fn fund (&mut self,account:String,amount:u128) -> Result<(), ContractError> {
+        assert_self()?;
+        let account_lts= self.lts_token.clone();
         ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
         .with_attached_deposit(1)
         .ft_transfer(account,(amount*100000000).to_string(),"".to_string());
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn context(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("energydao.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
     }
 
-}
\ No newline at end of file
+    fn dao() -> EnergyDao {
+        context("energydao.testnet");
+        let mut dao = EnergyDao::new("light-token.testnet".parse().unwrap());
+        dao.init().unwrap();
+        dao.add_community("alice.testnet".to_string()).unwrap();
+        dao
+    }
+
+    #[test]
+    fn invalid_calls_return_stable_errors() {
+        let mut dao = dao();
+        context("alice.testnet");
+        let error = dao.create_proposal(0, "grant".to_string(), "".to_string(), 10, 1, 0, 0).unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("council"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+        assert_eq!(dao.add_council("alice.testnet".to_string()).unwrap_err(), ContractError::Unauthorized("council"));
+        assert_eq!(dao.fund("alice.testnet".to_string(), 10).unwrap_err(), ContractError::Unauthorized("contract owner"));
+        assert_eq!(dao.delete_all().unwrap_err().code(), "UNAUTHORIZED");
+        assert_eq!(dao.add_vote("grant".to_string(), VoteChoice::Yes).unwrap_err(), ContractError::NotFound("proposal"));
+        assert_eq!(
+            dao.delegate("bob.testnet".to_string()).unwrap_err(),
+            ContractError::InvalidArgument("can only delegate to a dao member")
+        );
+
+        context("bob.testnet");
+        assert_eq!(dao.add_vote("grant".to_string(), VoteChoice::Yes).unwrap_err(), ContractError::Unauthorized("dao members"));
+        assert_eq!(dao.get_tally("grant".to_string()).unwrap_err().code(), "NOT_FOUND");
+    }
+}
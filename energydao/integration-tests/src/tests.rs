@@ -1,16 +1,20 @@
 use std::{env, fs};
 use near_units::parse_near;
-use serde_json::json;
+use serde_json::{json, Value};
 use workspaces::{Account, Contract};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let wasm_arg: &str = &(env::args().nth(1).unwrap());
     let wasm_filepath = fs::canonicalize(env::current_dir()?.join(wasm_arg))?;
+    let relay_arg: &str = &(env::args().nth(2).unwrap());
+    let relay_filepath = fs::canonicalize(env::current_dir()?.join(relay_arg))?;
 
     let worker = workspaces::sandbox().await?;
     let wasm = std::fs::read(wasm_filepath)?;
     let contract = worker.dev_deploy(&wasm).await?;
+    let relay_wasm = std::fs::read(relay_filepath)?;
+    let relay = worker.dev_deploy(&relay_wasm).await?;
 
     // create accounts
     let account = worker.dev_create_account().await?;
@@ -21,46 +25,152 @@ async fn main() -> anyhow::Result<()> {
         .await?
         .into_result()?;
 
+    // the contract account is the first council, alice joins the community
+    contract.call("new")
+        .args_json(json!({"lts_token": "light-token.testnet"}))
+        .transact()
+        .await?
+        .into_result()?;
+    contract.call("init").transact().await?.into_result()?;
+    contract.call("add_community")
+        .args_json(json!({"account": alice.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+    contract.call("create_proposal")
+        .args_json(json!({
+            "proposal_type": 0,
+            "proposal_name": "grant",
+            "description": "fund the pilot plant",
+            "amount": 10,
+            "duration_days": 1,
+            "duration_hours": 0,
+            "duration_min": 0,
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
     // begin tests
-    test_default_message(&alice, &contract).await?;
-    test_changes_message(&alice, &contract).await?;
+    test_community_cannot_add_members(&alice, &contract).await?;
+    test_only_the_dao_funds(&alice, &contract).await?;
+    test_relay_cannot_vote_for_member(&alice, &relay, &contract).await?;
+    test_relay_cannot_delegate_for_member(&alice, &relay, &contract).await?;
+    test_member_votes_directly(&alice, &contract).await?;
     Ok(())
 }
 
-async fn test_default_message(
+async fn get_tally(user: &Account, contract: &Contract) -> anyhow::Result<Value> {
+    let tally: Value = user
+        .call(contract.id(), "get_tally")
+        .args_json(json!({"proposal_name": "grant"}))
+        .transact()
+        .await?
+        .json()?;
+    Ok(tally)
+}
+
+async fn test_community_cannot_add_members(
     user: &Account,
     contract: &Contract,
 ) -> anyhow::Result<()> {
-    let message: String = user
-        .call( contract.id(), "get_greeting")
+    let outcome = user
+        .call(contract.id(), "add_community")
+        .args_json(json!({"account": "mallory.testnet"}))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    let communities: Vec<String> = user
+        .call(contract.id(), "get_communities")
         .args_json(json!({}))
         .transact()
         .await?
         .json()?;
+    assert_eq!(communities, vec![user.id().to_string()]);
+    println!("      Passed ✅ community can't add members");
+    Ok(())
+}
 
-    assert_eq!(message, "Hello".to_string());
-    println!("      Passed ✅ gets default message");
+async fn test_only_the_dao_funds(
+    user: &Account,
+    contract: &Contract,
+) -> anyhow::Result<()> {
+    let outcome = user
+        .call(contract.id(), "fund")
+        .args_json(json!({"account": user.id(), "amount": 10}))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+    println!("      Passed ✅ only the dao can fund");
     Ok(())
 }
 
-async fn test_changes_message(
+async fn test_relay_cannot_vote_for_member(
     user: &Account,
+    relay: &Contract,
     contract: &Contract,
 ) -> anyhow::Result<()> {
-    user.call(contract.id(), "set_greeting")
-        .args_json(json!({"message": "Howdy"}))
+    // alice signs the transaction, but the vote reaches the dao from the relay
+    let outcome = user
+        .call(relay.id(), "forward")
+        .args_json(json!({
+            "receiver_id": contract.id(),
+            "method_name": "add_vote",
+            "args": json!({"proposal_name": "grant", "vote": "Yes"}).to_string(),
+        }))
+        .max_gas()
         .transact()
-        .await?
-        .into_result()?;
+        .await?;
+    assert!(outcome.is_failure());
+
+    let tally = get_tally(user, contract).await?;
+    assert_eq!(tally["yes"], 0);
+    println!("      Passed ✅ relay can't vote for a member");
+    Ok(())
+}
+
+async fn test_relay_cannot_delegate_for_member(
+    user: &Account,
+    relay: &Contract,
+    contract: &Contract,
+) -> anyhow::Result<()> {
+    let outcome = user
+        .call(relay.id(), "forward")
+        .args_json(json!({
+            "receiver_id": contract.id(),
+            "method_name": "delegate",
+            "args": json!({"to": contract.id()}).to_string(),
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
 
-    let message: String = user
-        .call(contract.id(), "get_greeting")
+    let delegations: Vec<(String, String)> = user
+        .call(contract.id(), "get_delegations")
         .args_json(json!({}))
         .transact()
         .await?
         .json()?;
+    assert!(delegations.is_empty());
+    println!("      Passed ✅ relay can't delegate for a member");
+    Ok(())
+}
+
+async fn test_member_votes_directly(
+    user: &Account,
+    contract: &Contract,
+) -> anyhow::Result<()> {
+    user.call(contract.id(), "add_vote")
+        .args_json(json!({"proposal_name": "grant", "vote": "Yes"}))
+        .transact()
+        .await?
+        .into_result()?;
 
-    assert_eq!(message, "Howdy".to_string());
-    println!("      Passed ✅ changes message");
+    let tally = get_tally(user, contract).await?;
+    assert_eq!(tally["yes"], 1);
+    println!("      Passed ✅ member votes directly");
     Ok(())
-}
\ No newline at end of file
+}
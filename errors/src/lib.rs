@@ -0,0 +1,147 @@
+//! Errors shared by the Lightency contracts
+//!
+//! Methods return `Result<T, ContractError>` and are marked `#[handle_result]`, a failed call
+//! panics with `CODE: message`. Codes are stable, clients match on them rather than on the
+//! message, which may change.
+use near_sdk::FunctionError;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FunctionError)]
+pub enum ContractError {
+    AlreadyInitialized,
+    /// What was looked up, e.g. "dao" or "proposal"
+    NotFound(&'static str),
+    AlreadyExists(&'static str),
+    /// Who can call the method, e.g. "platform contract" or "council of the dao"
+    Unauthorized(&'static str),
+    AlreadyVoted,
+    /// The voting period is over or the proposal has been executed
+    VotingClosed,
+    /// The voting period is not over or the proposal was rejected
+    NotApproved,
+    ExecutionInProgress,
+    AlreadyExecuted,
+    DaoDissolved,
+    InsufficientBalance,
+    InsufficientDeposit,
+    /// A delay has not elapsed yet
+    TooEarly,
+    InvalidArgument(&'static str),
+    /// The call conflicts with the current state of the contract
+    InvalidState(&'static str),
+    /// A cross contract call failed, with the contract called
+    CallFailed(&'static str),
+}
+
+impl ContractError {
+    /// Stable code of the error, first part of the panic message
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::AlreadyInitialized => "ALREADY_INITIALIZED",
+            ContractError::NotFound(_) => "NOT_FOUND",
+            ContractError::AlreadyExists(_) => "ALREADY_EXISTS",
+            ContractError::Unauthorized(_) => "UNAUTHORIZED",
+            ContractError::AlreadyVoted => "ALREADY_VOTED",
+            ContractError::VotingClosed => "VOTING_CLOSED",
+            ContractError::NotApproved => "NOT_APPROVED",
+            ContractError::ExecutionInProgress => "EXECUTION_IN_PROGRESS",
+            ContractError::AlreadyExecuted => "ALREADY_EXECUTED",
+            ContractError::DaoDissolved => "DAO_DISSOLVED",
+            ContractError::InsufficientBalance => "INSUFFICIENT_BALANCE",
+            ContractError::InsufficientDeposit => "INSUFFICIENT_DEPOSIT",
+            ContractError::TooEarly => "TOO_EARLY",
+            ContractError::InvalidArgument(_) => "INVALID_ARGUMENT",
+            ContractError::InvalidState(_) => "INVALID_STATE",
+            ContractError::CallFailed(_) => "CALL_FAILED",
+        }
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            ContractError::AlreadyInitialized => write!(f, "contract is already initialized"),
+            ContractError::NotFound(what) => write!(f, "{} not found", what),
+            ContractError::AlreadyExists(what) => write!(f, "{} already exists", what),
+            ContractError::Unauthorized(who) => write!(f, "only the {} can call this method", who),
+            ContractError::AlreadyVoted => write!(f, "account has already voted"),
+            ContractError::VotingClosed => write!(f, "proposal is not open for votes"),
+            ContractError::NotApproved => write!(f, "proposal has not been approved"),
+            ContractError::ExecutionInProgress => write!(f, "proposal is being executed"),
+            ContractError::AlreadyExecuted => write!(f, "proposal has already been executed"),
+            ContractError::DaoDissolved => write!(f, "dao has been dissolved"),
+            ContractError::InsufficientBalance => write!(f, "balance is too low"),
+            ContractError::InsufficientDeposit => write!(f, "attached deposit is too low"),
+            ContractError::TooEarly => write!(f, "try again later"),
+            ContractError::InvalidArgument(message) | ContractError::InvalidState(message) => {
+                write!(f, "{}", message)
+            }
+            ContractError::CallFailed(contract) => write!(f, "call to the {} failed", contract),
+        }
+    }
+}
+
+/// Return `error` unless `condition` holds, the `Result` counterpart of `assert!`
+pub fn require(condition: bool, error: ContractError) -> Result<(), ContractError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_starts_with_the_code() {
+        assert_eq!(
+            ContractError::NotFound("dao").to_string(),
+            "NOT_FOUND: dao not found"
+        );
+        assert_eq!(
+            ContractError::Unauthorized("platform contract").to_string(),
+            "UNAUTHORIZED: only the platform contract can call this method"
+        );
+        assert_eq!(
+            ContractError::InvalidState("device is not approved").to_string(),
+            "INVALID_STATE: device is not approved"
+        );
+    }
+
+    #[test]
+    fn require_returns_the_error() {
+        assert_eq!(require(true, ContractError::TooEarly), Ok(()));
+        assert_eq!(
+            require(false, ContractError::TooEarly),
+            Err(ContractError::TooEarly)
+        );
+    }
+
+    #[test]
+    fn codes_are_stable() {
+        let codes = [
+            (ContractError::AlreadyInitialized, "ALREADY_INITIALIZED"),
+            (ContractError::NotFound("dao"), "NOT_FOUND"),
+            (ContractError::AlreadyExists("dao"), "ALREADY_EXISTS"),
+            (ContractError::Unauthorized("council"), "UNAUTHORIZED"),
+            (ContractError::AlreadyVoted, "ALREADY_VOTED"),
+            (ContractError::VotingClosed, "VOTING_CLOSED"),
+            (ContractError::NotApproved, "NOT_APPROVED"),
+            (ContractError::ExecutionInProgress, "EXECUTION_IN_PROGRESS"),
+            (ContractError::AlreadyExecuted, "ALREADY_EXECUTED"),
+            (ContractError::DaoDissolved, "DAO_DISSOLVED"),
+            (ContractError::InsufficientBalance, "INSUFFICIENT_BALANCE"),
+            (ContractError::InsufficientDeposit, "INSUFFICIENT_DEPOSIT"),
+            (ContractError::TooEarly, "TOO_EARLY"),
+            (ContractError::InvalidArgument("amount"), "INVALID_ARGUMENT"),
+            (ContractError::InvalidState("paused"), "INVALID_STATE"),
+            (ContractError::CallFailed("token"), "CALL_FAILED"),
+        ];
+        for (error, code) in codes {
+            assert_eq!(error.code(), code);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Types and cross-contract interfaces shared by the Fractose factory and the shares contracts
+//!
+//! Each contract implements its side of the interface with `#[near_bindgen] impl`, and the
+//! other side calls it through the generated `ext_*` module, so both are checked against the
+//! same signatures at compile time.
+use lightency_errors::ContractError;
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{ext_contract, AccountId, Promise};
+
+pub type TokenId = String;
+
+/// Metadata of the share fungible token, chosen at securitization
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ShareTokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Arguments of `FractoseShares::create`, `n_shares` of `shares_count` are sent to
+/// `share_holders` and the rest to `owner_id`
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreateSharesArgs {
+    pub nft_contract_address: AccountId,
+    pub nft_token_id: TokenId,
+    pub owner_id: AccountId,
+    pub shares_count: U128,
+    pub decimals: u8,
+    pub share_price: U128,
+    pub share_holders: Vec<AccountId>,
+    pub n_shares: Vec<U128>,
+    pub payment_token: Option<AccountId>,
+    /// Revenue is accepted in NEAR and in this token if given
+    pub lts_token: Option<AccountId>,
+    pub token_metadata: ShareTokenMetadata,
+}
+
+impl CreateSharesArgs {
+    /// JSON input of `create`
+    ///
+    /// The factory calls `create` in the batch creating the shares account, which the
+    /// `ext_shares` interface cannot add actions to.
+    pub fn to_input(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Input<'a> {
+            args: &'a CreateSharesArgs,
+        }
+
+        near_sdk::serde_json::to_vec(&Input { args: self })
+            .expect("shares arguments are serializable")
+    }
+}
+
+/// Shares contract methods called by the factory
+#[ext_contract(ext_shares)]
+pub trait FractoseShares {
+    /// Initialize the shares of an NFT
+    ///
+    /// The factory calls it with `CreateSharesArgs::to_input`, whose single `args` field
+    /// must keep the name of the parameter.
+    fn create(args: CreateSharesArgs) -> Result<Self, ContractError>
+    where
+        Self: Sized;
+
+    /// Delete the account when the factory could not move the NFT to it
+    fn abort(&mut self) -> Result<Promise, ContractError>;
+}
+
+/// Factory methods called by the shares contracts it deployed
+#[ext_contract(ext_factory)]
+pub trait FractoseFactory {
+    /// The NFT has been redeemed or auctioned
+    fn report_redeemed(&mut self) -> Result<(), ContractError>;
+
+    /// The shares contract is about to delete its account
+    fn report_closed(&mut self) -> Result<(), ContractError>;
+
+    /// Upgrade a shares contract to a stored code version
+    fn upgrade_shares(
+        &mut self,
+        shares_account: AccountId,
+        version: u32,
+    ) -> Result<Promise, ContractError>;
+}
@@ -1,53 +1,230 @@
+use fractose_common::{ext_shares, CreateSharesArgs, FractoseFactory, ShareTokenMetadata, TokenId};
+use lightency_errors::{require, ContractError};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LookupMap;
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap};
 use near_sdk::ext_contract;
-use near_sdk::json_types::{U128, U64};
-use near_sdk::{env, log, near_bindgen, AccountId, BorshStorageKey, Gas, Promise};
-use std::convert::TryInto;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{
+    env, log, near_bindgen, AccountId, Balance, BorshStorageKey, FunctionError, Gas, Promise,
+    PromiseError, PromiseOrValue,
+};
 
 pub const TGAS: u64 = 1_000_000_000_000;
-pub type TokenId = String;
+pub const GAS_FOR_NFT_TRANSFER: Gas = Gas(10 * TGAS);
+pub const GAS_FOR_NFT_TOKEN: Gas = Gas(5 * TGAS);
+pub const GAS_FOR_SHARES_CREATE: Gas = Gas(20 * TGAS);
+pub const GAS_FOR_ON_SECURITIZE_COMPLETE: Gas = Gas(40 * TGAS);
+pub const GAS_FOR_ON_SHARES_CREATED: Gas = Gas(60 * TGAS);
+pub const GAS_FOR_ON_NFT_ESCROWED: Gas = Gas(100 * TGAS);
+pub const GAS_FOR_SHARES_UPGRADE: Gas = Gas(100 * TGAS);
+pub const GAS_FOR_ON_SHARES_UPGRADED: Gas = Gas(10 * TGAS);
+pub const GAS_FOR_SHARES_ABORT: Gas = Gas(10 * TGAS);
+pub const GAS_FOR_ON_SHARES_ABORTED: Gas = Gas(10 * TGAS);
+/// NEAR required to create and fund a shares sub-account
+pub const SHARES_ACCOUNT_DEPOSIT: Balance = 25_00000000000000000000000;
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
 pub type AccountAndTokenId = String;
 
-#[ext_contract(shares)]
-pub trait Shares {
-    fn create(
-        &mut self,
-        nft_contract_address: AccountId,
-        nft_token_id: TokenId,
-        owner_id: AccountId,
-        shares_count: U128,
-        decimals: u8,
-        share_price: U128,
-        share_holders: Vec<AccountId>,
-        n_shares: Vec<U128>,
-    ) -> Self;
-}
-
 #[ext_contract(non_fungible_token)]
 pub trait NonFungibleTokenCore {
     fn nft_transfer(
         &mut self,
         receiver_id: AccountId,
         token_id: TokenId,
-        approval_id: Option<U64>,
+        approval_id: Option<u64>,
         memo: Option<String>,
     );
+
+    fn nft_token(&self, token_id: TokenId) -> Option<Token>;
+}
+
+/// Subset of the NEP-171 `Token` view needed to check ownership
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Token {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub metadata: Option<TokenMetadata>,
+}
+
+/// Subset of the NEP-177 token metadata used to describe shares
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub title: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Shares parameters registered with `request_securitize` and repeated in the `msg` of `nft_approve`
+#[derive(Deserialize, Serialize, BorshDeserialize, BorshSerialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SecuritizeArgs {
+    /// Number of fungible shares to be created
+    pub shares_count: U128,
+    /// Number of decimal places in share fungible tokens
+    pub decimals: u8,
+    /// Underlying NFT can be retrieved by paying the exit price
+    pub exit_price: U128,
+    /// Initial distribution of shares, the owner keeps the unallocated remainder
+    #[serde(default)]
+    pub share_holders: Vec<ShareAllocation>,
+    /// NEP-141 token the exit price is paid in, such as LTS. Defaults to NEAR
+    #[serde(default)]
+    pub payment_token: Option<AccountId>,
+    /// Share token name, derived from the NFT title if missing
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Share token symbol, derived from the NFT title if missing
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// Share token icon as a data URL
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Off-chain metadata, defaults to the reference of the NFT metadata
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Shares handed to an account when the shares contract is created
+#[derive(Deserialize, Serialize, BorshDeserialize, BorshSerialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ShareAllocation {
+    pub account_id: AccountId,
+    pub n_shares: U128,
+}
+
+impl SecuritizeArgs {
+    pub fn assert_valid(&self, owner_id: &AccountId) -> Result<(), ContractError> {
+        require(
+            self.exit_price.0 > 0,
+            ContractError::InvalidArgument("invalid exit price"),
+        )?;
+        require(
+            self.shares_count.0 > 0,
+            ContractError::InvalidArgument("invalid shares count"),
+        )?;
+        require(
+            self.exit_price.0 % self.shares_count.0 == 0,
+            ContractError::InvalidArgument("share price cannot be fractional"),
+        )?;
+
+        let mut allocated: u128 = 0;
+        for (i, holder) in self.share_holders.iter().enumerate() {
+            require(
+                holder.account_id != *owner_id,
+                ContractError::InvalidArgument("owner cannot be a share holder"),
+            )?;
+            require(
+                self.share_holders[..i]
+                    .iter()
+                    .all(|other| other.account_id != holder.account_id),
+                ContractError::InvalidArgument("duplicate share holder"),
+            )?;
+            allocated = allocated
+                .checked_add(holder.n_shares.0)
+                .ok_or(ContractError::InvalidArgument("allocation overflow"))?;
+        }
+        require(
+            allocated <= self.shares_count.0,
+            ContractError::InvalidArgument("allocated shares exceed shares count"),
+        )
+    }
+
+    pub fn share_price(&self) -> u128 {
+        self.exit_price.0 / self.shares_count.0
+    }
+
+    /// Share token metadata, falling back to the metadata of the NFT being securitized
+    pub fn share_metadata(&self, token: &Token) -> ShareTokenMetadata {
+        let nft_metadata = token.metadata.as_ref();
+        let title = nft_metadata
+            .and_then(|metadata| metadata.title.clone())
+            .unwrap_or_else(|| token.token_id.clone());
+
+        let (reference, reference_hash) = if self.reference.is_some() {
+            (self.reference.clone(), self.reference_hash.clone())
+        } else {
+            // NEP-177 makes the hash optional, NEP-148 share metadata requires it with the reference
+            nft_metadata
+                .filter(|metadata| {
+                    metadata.reference.is_some() && metadata.reference_hash.is_some()
+                })
+                .map(|metadata| (metadata.reference.clone(), metadata.reference_hash.clone()))
+                .unwrap_or((None, None))
+        };
+
+        ShareTokenMetadata {
+            name: self
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{} shares", title)),
+            symbol: self.symbol.clone().unwrap_or_else(|| derive_symbol(&title)),
+            icon: self.icon.clone(),
+            reference,
+            reference_hash,
+        }
+    }
+}
+
+/// Lifecycle of a shares contract
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SharesStatus {
+    /// Shares are backed by the NFT
+    Active,
+    /// The NFT has been released, holders claim the vault
+    Redeemed,
+    /// All shares are burnt and the shares account is deleted
+    Closed,
+    /// The NFT could not be moved to the shares contract, which could not be deleted either.
+    /// The owner of Fractose retries with `abort_orphaned_shares`
+    Orphaned,
+}
+
+/// Registry entry of a securitized NFT
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SecuritizedNft {
+    pub shares_contract: AccountId,
+    pub nft_contract_address: AccountId,
+    pub nft_token_id: TokenId,
+    pub owner_id: AccountId,
+    pub status: SharesStatus,
+    /// Version of the shares code currently deployed
+    pub code_version: u32,
 }
 
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKeyEnum {
     NftToSharesAddress,
-    SharesToNftAddress,
+    Securitized,
+    Requests,
+    Allocations,
+    SharesCode,
+    Pending,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Fractose {
     pub nft_to_shares_address: LookupMap<AccountAndTokenId, AccountId>,
-    pub shares_to_nft_address: LookupMap<AccountId, AccountAndTokenId>,
-    pub sharess_holders: UnorderedMap<AccountId, U128>,
+    pub securitized: UnorderedMap<AccountId, SecuritizedNft>,
+    /// Securitizations requested by NFT owners, each funded with `SHARES_ACCOUNT_DEPOSIT`,
+    /// keyed by owner and NFT
+    pub requests: LookupMap<String, SecuritizeArgs>,
+    /// Initial share allocation of each shares contract
+    pub allocations: LookupMap<AccountId, Vec<ShareAllocation>>,
+    /// Shares contract code blobs by version
+    pub shares_code: LookupMap<u32, Vec<u8>>,
+    pub latest_shares_version: u32,
+    /// NFTs being escrowed and securitized, locked until the flow completes or is undone
+    pub pending: LookupSet<AccountAndTokenId>,
+    /// LTS token that new shares contracts accept as revenue
+    pub lts_token: Option<AccountId>,
 }
 
 impl Default for Fractose {
@@ -56,34 +233,523 @@ impl Default for Fractose {
             nft_to_shares_address: LookupMap::<AccountAndTokenId, AccountId>::new(
                 StorageKeyEnum::NftToSharesAddress,
             ),
-            shares_to_nft_address: LookupMap::<AccountId, AccountAndTokenId>::new(
-                StorageKeyEnum::SharesToNftAddress,
-            ),
-            sharess_holders: UnorderedMap::new(b"share_holders".to_vec()),
+            securitized: UnorderedMap::new(StorageKeyEnum::Securitized),
+            requests: LookupMap::new(StorageKeyEnum::Requests),
+            allocations: LookupMap::new(StorageKeyEnum::Allocations),
+            shares_code: LookupMap::new(StorageKeyEnum::SharesCode),
+            latest_shares_version: 0,
+            pending: LookupSet::new(StorageKeyEnum::Pending),
+            lts_token: None,
+        }
+    }
+}
+
+// Make sure that the caller of the function is the owner
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
+}
+
+#[near_bindgen]
+impl Fractose {
+    /// Store a new version of the shares contract code, passed as raw input
+    ///
+    /// New securitizations deploy the latest version.
+    #[handle_result]
+    pub fn upload_shares_code(&mut self) -> Result<u32, ContractError> {
+        assert_self()?;
+        let code = env::input()
+            .filter(|code| !code.is_empty())
+            .ok_or(ContractError::InvalidArgument("missing shares code"))?;
+
+        let version = self.latest_shares_version + 1;
+        self.shares_code.insert(&version, &code);
+        self.latest_shares_version = version;
+        log!("Uploaded shares code version {}", version);
+        Ok(version)
+    }
+
+    /// Set the LTS token passed to shares contracts created from now on
+    #[handle_result]
+    pub fn set_lts_token(&mut self, lts_token: AccountId) -> Result<(), ContractError> {
+        assert_self()?;
+        self.lts_token = Some(lts_token);
+        Ok(())
+    }
+
+    /// Record the code version of an upgraded shares contract
+    #[private]
+    pub fn on_shares_upgraded(
+        &mut self,
+        #[callback_result] upgraded: Result<(), PromiseError>,
+        shares_account: AccountId,
+        version: u32,
+    ) -> bool {
+        if upgraded.is_err() {
+            log!("Shares contract {} could not be upgraded", shares_account);
+            return false;
+        }
+        if let Some(mut entry) = self.securitized.get(&shares_account) {
+            entry.code_version = version;
+            self.securitized.insert(&shares_account, &entry);
+        }
+        true
+    }
+
+    /// Request the securitization of an NFT, attaching the NEAR that funds its shares contract
+    ///
+    /// `nft_approve` does not forward its deposit to the approved account, and the
+    /// owner it reports is only as trustworthy as the calling contract. The deposit is
+    /// spent only when the owner's NFT is approved with these exact `args`.
+    /// NEAR attached beyond `SHARES_ACCOUNT_DEPOSIT` is refunded.
+    #[payable]
+    #[handle_result]
+    pub fn request_securitize(
+        &mut self,
+        nft_contract_address: AccountId,
+        token_id: TokenId,
+        args: SecuritizeArgs,
+    ) -> Result<(), ContractError> {
+        let owner_id = env::predecessor_account_id();
+        args.assert_valid(&owner_id)?;
+        let attached = env::attached_deposit();
+        require(
+            attached >= SHARES_ACCOUNT_DEPOSIT,
+            ContractError::InsufficientDeposit,
+        )?;
+        let key = get_request_key(&owner_id, nft_contract_address, token_id);
+        require(
+            !self.requests.contains_key(&key),
+            ContractError::AlreadyExists("securitize request"),
+        )?;
+        self.requests.insert(&key, &args);
+        if attached > SHARES_ACCOUNT_DEPOSIT {
+            Promise::new(owner_id).transfer(attached - SHARES_ACCOUNT_DEPOSIT);
+        }
+        Ok(())
+    }
+
+    /// Cancel a securitization request that was not consumed and refund its deposit
+    #[handle_result]
+    pub fn cancel_securitize(
+        &mut self,
+        nft_contract_address: AccountId,
+        token_id: TokenId,
+    ) -> Result<Promise, ContractError> {
+        let owner_id = env::predecessor_account_id();
+        self.requests
+            .remove(&get_request_key(&owner_id, nft_contract_address, token_id))
+            .ok_or(ContractError::NotFound("securitize request"))?;
+        Ok(Promise::new(owner_id).transfer(SHARES_ACCOUNT_DEPOSIT))
+    }
+
+    /// Pending securitization request of an owner for an NFT
+    pub fn get_securitize_request(
+        &self,
+        owner_id: AccountId,
+        nft_contract_address: AccountId,
+        token_id: TokenId,
+    ) -> Option<SecuritizeArgs> {
+        self.requests
+            .get(&get_request_key(&owner_id, nft_contract_address, token_id))
+    }
+
+    /// Shares contract of a securitized NFT
+    pub fn get_shares_contract(
+        &self,
+        nft_contract_address: AccountId,
+        token_id: TokenId,
+    ) -> Option<AccountId> {
+        self.nft_to_shares_address
+            .get(&get_nft_address(nft_contract_address, token_id))
+    }
+
+    /// NFT backing a shares contract, with its lifecycle status
+    pub fn get_nft_for_shares(&self, shares_contract: AccountId) -> Option<SecuritizedNft> {
+        self.securitized.get(&shares_contract)
+    }
+
+    /// Paginated list of securitized NFTs
+    pub fn list_securitized(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<SecuritizedNft> {
+        let from_index = from_index.map(|index| index.0).unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        self.securitized
+            .values_as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Initial share allocation of a shares contract
+    pub fn get_allocation(&self, shares_contract: AccountId) -> Vec<ShareAllocation> {
+        self.allocations.get(&shares_contract).unwrap_or_default()
+    }
+
+    /// NEP-178 approval receiver, securitizes the approved NFT into shares
+    ///
+    /// The caller is the NFT contract. `msg` is a JSON encoded `SecuritizeArgs`, which must
+    /// match the request the owner registered for this NFT with `request_securitize`.
+    /// The NFT is first escrowed in Fractose, shares are created once
+    /// `on_nft_escrowed` has verified that Fractose owns the token.
+    /// NEP-178 fixes the return type, errors panic with their code instead of being returned.
+    pub fn nft_on_approve(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        msg: String,
+    ) -> PromiseOrValue<()> {
+        let nft_contract_address = env::predecessor_account_id();
+        near_sdk::serde_json::from_str(&msg)
+            .map_err(|_| ContractError::InvalidArgument("invalid securitize message"))
+            .and_then(|args| {
+                self.securitize(nft_contract_address, token_id, owner_id, approval_id, args)
+            })
+            .unwrap_or_else(|error| error.panic())
+            .into()
+    }
+
+    /// Verify that the escrow transfer succeeded and deploy the shares contract
+    #[private]
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_nft_escrowed(
+        &mut self,
+        #[callback_result] token: Result<Option<Token>, PromiseError>,
+        nft_contract_address: AccountId,
+        nft_token_id: TokenId,
+        owner_id: AccountId,
+        shares_contract: AccountId,
+        args: SecuritizeArgs,
+        code_version: u32,
+    ) -> PromiseOrValue<bool> {
+        let token = match token {
+            Ok(Some(token)) if token.owner_id == env::current_account_id() => token,
+            Ok(Some(_)) => {
+                // Someone else owns the token, the escrow transfer did not happen
+                log!(
+                    "Token {} from contract {} could not be escrowed",
+                    nft_token_id,
+                    nft_contract_address
+                );
+                self.pending
+                    .remove(&get_nft_address(nft_contract_address, nft_token_id));
+                Promise::new(owner_id).transfer(SHARES_ACCOUNT_DEPOSIT);
+                return PromiseOrValue::Value(false);
+            }
+            _ => {
+                // The owner could not be read back, the token may be escrowed already
+                log!(
+                    "Escrow of token {} from contract {} could not be verified",
+                    nft_token_id,
+                    nft_contract_address
+                );
+                self.undo_securitize(nft_contract_address, nft_token_id, owner_id, true);
+                return PromiseOrValue::Value(false);
+            }
+        };
+
+        let code = match self.shares_code.get(&code_version) {
+            Some(code) => code,
+            None => {
+                log!("Shares code version {} is missing", code_version);
+                self.undo_securitize(nft_contract_address, nft_token_id, owner_id, true);
+                return PromiseOrValue::Value(false);
+            }
+        };
+
+        let create_args = CreateSharesArgs {
+            nft_contract_address: nft_contract_address.clone(),
+            nft_token_id: nft_token_id.clone(),
+            owner_id: owner_id.clone(),
+            shares_count: args.shares_count,
+            decimals: args.decimals,
+            share_price: U128(args.share_price()),
+            share_holders: args
+                .share_holders
+                .iter()
+                .map(|holder| holder.account_id.clone())
+                .collect(),
+            n_shares: args
+                .share_holders
+                .iter()
+                .map(|holder| holder.n_shares)
+                .collect(),
+            payment_token: args.payment_token.clone(),
+            lts_token: self.lts_token.clone(),
+            token_metadata: args.share_metadata(&token),
+        };
+
+        log!("{}", shares_contract);
+        // Deploy latest shares code and call its constructor in one batch, so that the
+        // account is not created if `create` fails. The account gets no access key,
+        // only the shares code and Fractose through it can act on it.
+        Promise::new(shares_contract.clone())
+            .create_account()
+            .transfer(SHARES_ACCOUNT_DEPOSIT)
+            .deploy_contract(code)
+            .function_call(
+                "create".to_string(),
+                create_args.to_input(),
+                0,
+                GAS_FOR_SHARES_CREATE,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_SHARES_CREATED)
+                    .on_shares_created(
+                        nft_contract_address,
+                        nft_token_id,
+                        owner_id,
+                        shares_contract,
+                        args.share_holders,
+                        code_version,
+                    ),
+            )
+            .into()
+    }
+
+    /// Move the escrowed NFT to the shares contract once it is deployed and initialized
+    #[private]
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_shares_created(
+        &mut self,
+        #[callback_result] created: Result<(), PromiseError>,
+        nft_contract_address: AccountId,
+        nft_token_id: TokenId,
+        owner_id: AccountId,
+        shares_contract: AccountId,
+        share_holders: Vec<ShareAllocation>,
+        code_version: u32,
+    ) -> PromiseOrValue<bool> {
+        if created.is_err() {
+            // The batch was reverted, the deposit came back with the account creation
+            log!("Shares contract {} could not be created", shares_contract);
+            self.undo_securitize(nft_contract_address, nft_token_id, owner_id, true);
+            return PromiseOrValue::Value(false);
         }
+
+        non_fungible_token::ext(nft_contract_address.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(shares_contract.clone(), nft_token_id.clone(), None, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_SECURITIZE_COMPLETE)
+                    .on_securitize_complete(
+                        nft_contract_address,
+                        nft_token_id,
+                        owner_id,
+                        shares_contract,
+                        share_holders,
+                        code_version,
+                    ),
+            )
+            .into()
+    }
+
+    /// Commit the registry entries once the shares contract holds the NFT
+    #[private]
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_securitize_complete(
+        &mut self,
+        #[callback_result] transferred: Result<(), PromiseError>,
+        nft_contract_address: AccountId,
+        nft_token_id: TokenId,
+        owner_id: AccountId,
+        shares_contract: AccountId,
+        share_holders: Vec<ShareAllocation>,
+        code_version: u32,
+    ) -> bool {
+        if transferred.is_err() {
+            log!(
+                "Token {} could not be moved to {}",
+                nft_token_id,
+                shares_contract
+            );
+            self.undo_securitize(
+                nft_contract_address.clone(),
+                nft_token_id.clone(),
+                owner_id.clone(),
+                false,
+            );
+
+            // Record the shares contract until it is deleted, the deposit is refunded then
+            self.securitized.insert(
+                &shares_contract,
+                &SecuritizedNft {
+                    shares_contract: shares_contract.clone(),
+                    nft_contract_address,
+                    nft_token_id,
+                    owner_id,
+                    status: SharesStatus::Orphaned,
+                    code_version,
+                },
+            );
+            self.abort_shares(shares_contract);
+            return false;
+        }
+
+        // Save metadata
+        let nft_address = get_nft_address(nft_contract_address.clone(), nft_token_id.clone());
+        self.pending.remove(&nft_address);
+
+        self.nft_to_shares_address
+            .insert(&nft_address, &shares_contract);
+        self.securitized.insert(
+            &shares_contract,
+            &SecuritizedNft {
+                shares_contract: shares_contract.clone(),
+                nft_contract_address,
+                nft_token_id,
+                owner_id,
+                status: SharesStatus::Active,
+                code_version,
+            },
+        );
+        self.allocations.insert(&shares_contract, &share_holders);
+
+        log!("Securitized {} into {}", nft_address, shares_contract);
+        true
     }
 }
 
 #[near_bindgen]
 impl Fractose {
+    /// Retry deleting a shares contract left without its NFT
+    #[handle_result]
+    pub fn abort_orphaned_shares(
+        &mut self,
+        shares_account: AccountId,
+    ) -> Result<Promise, ContractError> {
+        assert_self()?;
+        let entry = self
+            .securitized
+            .get(&shares_account)
+            .ok_or(ContractError::NotFound("shares contract"))?;
+        require(
+            entry.status == SharesStatus::Orphaned,
+            ContractError::InvalidState("shares contract is not orphaned"),
+        )?;
+        Ok(self.abort_shares(shares_account))
+    }
+
+    /// Drop the registry entry of a deleted orphaned shares contract and refund its deposit
+    #[private]
+    pub fn on_shares_aborted(
+        &mut self,
+        #[callback_result] aborted: Result<(), PromiseError>,
+        shares_account: AccountId,
+    ) -> bool {
+        if aborted.is_err() {
+            log!("Shares contract {} could not be deleted", shares_account);
+            return false;
+        }
+        if let Some(entry) = self.securitized.remove(&shares_account) {
+            Promise::new(entry.owner_id).transfer(SHARES_ACCOUNT_DEPOSIT);
+        }
+        true
+    }
+}
+
+#[near_bindgen]
+impl FractoseFactory for Fractose {
+    /// Called by a shares contract once its NFT has been redeemed or auctioned
+    #[handle_result]
+    fn report_redeemed(&mut self) -> Result<(), ContractError> {
+        let mut entry = self.assert_shares_contract()?;
+        require(
+            entry.status == SharesStatus::Active,
+            ContractError::InvalidState("shares already redeemed"),
+        )?;
+        entry.status = SharesStatus::Redeemed;
+        self.securitized.insert(&entry.shares_contract, &entry);
+        Ok(())
+    }
+
+    /// Called by a shares contract before it deletes its account
+    ///
+    /// The NFT can be securitized again once its shares contract is closed.
+    #[handle_result]
+    fn report_closed(&mut self) -> Result<(), ContractError> {
+        let mut entry = self.assert_shares_contract()?;
+        entry.status = SharesStatus::Closed;
+        self.securitized.insert(&entry.shares_contract, &entry);
+        self.nft_to_shares_address.remove(&get_nft_address(
+            entry.nft_contract_address,
+            entry.nft_token_id,
+        ));
+        Ok(())
+    }
+
+    /// Upgrade a shares contract to a stored code version
+    ///
+    /// Called by the owner, or by the shares contract itself once its holders approved.
+    #[handle_result]
+    fn upgrade_shares(
+        &mut self,
+        shares_account: AccountId,
+        version: u32,
+    ) -> Result<Promise, ContractError> {
+        let entry = self
+            .securitized
+            .get(&shares_account)
+            .ok_or(ContractError::NotFound("shares contract"))?;
+        let caller = env::predecessor_account_id();
+        require(
+            caller == env::current_account_id() || caller == shares_account,
+            ContractError::Unauthorized("owner or the shares contract"),
+        )?;
+        require(
+            entry.status != SharesStatus::Closed,
+            ContractError::InvalidState("shares contract is closed"),
+        )?;
+        let code = self
+            .shares_code
+            .get(&version)
+            .ok_or(ContractError::NotFound("shares code version"))?;
+
+        Ok(Promise::new(shares_account.clone())
+            .function_call("upgrade".to_string(), code, 0, GAS_FOR_SHARES_UPGRADE)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_SHARES_UPGRADED)
+                    .on_shares_upgraded(shares_account, version),
+            ))
+    }
+}
+
+impl Fractose {
+    /// Registry entry of the calling shares contract
+    fn assert_shares_contract(&self) -> Result<SecuritizedNft, ContractError> {
+        self.securitized
+            .get(&env::predecessor_account_id())
+            .ok_or(ContractError::Unauthorized("shares contracts"))
+    }
+
     /// Securitize an approved NFT into shares
     ///
     /// # Parameters
     ///
     /// - `nft_contract_address`: Address of NFT contract
     /// - `nft_token_id`: Address of the NFT to be securitized
-    /// - `shares_count`: Number of fungible shares to be created
-    /// - `decimals`: Number of decimal places in share fungible tokens
-    /// - `exit_price`: Underlying NFT can be retrieved by paying the exit price
-    #[payable]
-    pub fn securitize(
+    /// - `owner_id`: Current owner of the NFT, as reported by the NFT contract
+    /// - `approval_id`: Approval given to Fractose for the NFT
+    /// - `args`: Shares parameters
+    fn securitize(
         &mut self,
-        nft_contract_address: String,
+        nft_contract_address: AccountId,
         nft_token_id: TokenId,
-        shares_count: U128,
-        decimals: u8,
-        exit_price: U128,
-    ) {
+        owner_id: AccountId,
+        approval_id: u64,
+        args: SecuritizeArgs,
+    ) -> Result<Promise, ContractError> {
         log!(
             "Securitizing token {} from contract {}",
             nft_token_id,
@@ -91,70 +757,114 @@ impl Fractose {
         );
 
         // Check whether parameters are valid
-        assert!(exit_price.0 > 0, "invalid exit price");
-        assert!(shares_count.0 > 0, "invalid shares count");
-        assert!(
-            exit_price.0 % shares_count.0 == 0,
-            "share price cannot be fractional"
-        );
+        args.assert_valid(&owner_id)?;
+        // Check the code before escrowing, so the callback can deploy it
+        let code_version = self.latest_shares_version;
+        require(
+            self.shares_code.contains_key(&code_version),
+            ContractError::InvalidState("no shares code uploaded"),
+        )?;
+        log!("Share price: {}", args.share_price());
 
-        let share_price = exit_price.0 / shares_count.0;
-        log!("Share price: {}", share_price);
+        let nft_address = get_nft_address(nft_contract_address.clone(), nft_token_id.clone());
+        require(
+            self.nft_to_shares_address.get(&nft_address).is_none(),
+            ContractError::AlreadyExists("shares of the token"),
+        )?;
+        require(
+            !self.pending.contains(&nft_address),
+            ContractError::ExecutionInProgress,
+        )?;
+        let shares_contract: AccountId =
+            get_shares_contract_name(nft_contract_address.to_string(), nft_token_id.clone())
+                .parse()
+                .map_err(|_| ContractError::InvalidArgument("invalid shares account name"))?;
 
-        // Include NFT ID
-        let shares_contract =
-            get_shares_contract_name(nft_contract_address.clone(), nft_token_id.clone());
-        
-        log!("{}", shares_contract);
-        // Deploy shares contract
-        Promise::new(shares_contract.clone().parse().unwrap())
-            .create_account()
-            .transfer(25_00000000000000000000000)
-            .add_full_access_key(env::signer_account_pk())
-            .deploy_contract(include_bytes!("../../shares/res/nft_shares.wasm").to_vec());
-
-        
-        let owner: AccountId = env::signer_account_id().try_into().unwrap();
-        
-        // Call shares contract constructor
-        
-        shares::ext(shares_contract.clone().try_into().unwrap())
-            .with_static_gas(Gas(2 * TGAS))
-            .create(
-                nft_contract_address.clone().try_into().unwrap(),
-                nft_token_id.clone(),
-                owner,
-                shares_count,
-                decimals,
-                share_price.into(),
-                self.sharess_holders.keys_as_vector().to_vec(),
-                self.sharess_holders.values_as_vector().to_vec(),
-            );
-
-        // Save metadata
-        let nft_address = get_nft_address(
-            nft_contract_address.try_into().unwrap(),
+        // Consume the request funding the shares contract. It is keyed by the calling NFT
+        // contract, so another contract reporting the same owner cannot spend it
+        let request_key = get_request_key(
+            &owner_id,
+            nft_contract_address.clone(),
             nft_token_id.clone(),
         );
+        let request = self
+            .requests
+            .get(&request_key)
+            .ok_or(ContractError::NotFound("securitize request"))?;
+        require(
+            request == args,
+            ContractError::InvalidArgument("approval does not match the securitize request"),
+        )?;
+        self.requests.remove(&request_key);
 
-        self.nft_to_shares_address
-            .insert(&nft_address, &shares_contract.clone().try_into().unwrap());
-        self.shares_to_nft_address
-            .insert(&shares_contract.clone().try_into().unwrap(), &nft_address);
+        // Lock the token so that a concurrent approval cannot pass the ownership check below
+        self.pending.insert(&nft_address);
 
-        non_fungible_token::ext("nft-lightency.testnet".to_string().try_into().unwrap())
+        // Escrow the NFT, then read back its owner to verify the transfer
+        Ok(non_fungible_token::ext(nft_contract_address.clone())
             .with_attached_deposit(1)
-            .with_static_gas(Gas(2 * TGAS))
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
             .nft_transfer(
-                shares_contract.clone().try_into().unwrap(),
+                env::current_account_id(),
                 nft_token_id.clone(),
+                Some(approval_id),
                 None,
-                None,
-            );
+            )
+            .then(
+                non_fungible_token::ext(nft_contract_address.clone())
+                    .with_static_gas(GAS_FOR_NFT_TOKEN)
+                    .nft_token(nft_token_id.clone()),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_NFT_ESCROWED)
+                    .on_nft_escrowed(
+                        nft_contract_address,
+                        nft_token_id,
+                        owner_id,
+                        shares_contract,
+                        args,
+                        code_version,
+                    ),
+            ))
+    }
+
+    /// Ask a shares contract whose NFT never arrived to delete its account
+    fn abort_shares(&self, shares_account: AccountId) -> Promise {
+        ext_shares::ext(shares_account.clone())
+            .with_static_gas(GAS_FOR_SHARES_ABORT)
+            .abort()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_SHARES_ABORTED)
+                    .on_shares_aborted(shares_account),
+            )
     }
 
-    pub fn fill_share_holders(&mut self, sh_id: AccountId, nshares: U128) {
-        self.sharess_holders.insert(&sh_id, &nshares);
+    /// Return the escrowed NFT to its owner, and the deposit if it was not spent
+    fn undo_securitize(
+        &mut self,
+        nft_contract_address: AccountId,
+        nft_token_id: TokenId,
+        owner_id: AccountId,
+        refund_deposit: bool,
+    ) {
+        self.pending.remove(&get_nft_address(
+            nft_contract_address.clone(),
+            nft_token_id.clone(),
+        ));
+        non_fungible_token::ext(nft_contract_address)
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(
+                owner_id.clone(),
+                nft_token_id,
+                None,
+                Some("securitization failed".to_string()),
+            );
+        if refund_deposit {
+            Promise::new(owner_id).transfer(SHARES_ACCOUNT_DEPOSIT);
+        }
     }
 }
 
@@ -163,6 +873,454 @@ fn get_shares_contract_name(_target: String, token_id: TokenId) -> String {
     format!("{}-{}.{}", prefix, token_id, env::current_account_id())
 }
 
+/// Upper case alphanumeric prefix of an NFT title, used as share symbol
+fn derive_symbol(title: &str) -> String {
+    let symbol: String = title
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(8)
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if symbol.is_empty() {
+        "SHARES".to_string()
+    } else {
+        symbol
+    }
+}
+
 fn get_nft_address(contract_address: AccountId, token_id: TokenId) -> String {
     format!("{}/{}", contract_address, token_id)
-}
\ No newline at end of file
+}
+
+fn get_request_key(owner_id: &AccountId, contract_address: AccountId, token_id: TokenId) -> String {
+    format!(
+        "{}:{}",
+        owner_id,
+        get_nft_address(contract_address, token_id)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn context(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("fractose.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    fn args(
+        shares_count: u128,
+        exit_price: u128,
+        share_holders: Vec<ShareAllocation>,
+    ) -> SecuritizeArgs {
+        SecuritizeArgs {
+            shares_count: U128(shares_count),
+            decimals: 0,
+            exit_price: U128(exit_price),
+            share_holders,
+            payment_token: None,
+            name: None,
+            symbol: None,
+            icon: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn owner_methods_reject_other_callers() {
+        context("alice.testnet");
+        let mut fractose = Fractose::default();
+        let error = fractose
+            .set_lts_token("light-token.testnet".parse().unwrap())
+            .unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("contract owner"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+        assert_eq!(
+            fractose.upload_shares_code().unwrap_err().code(),
+            "UNAUTHORIZED"
+        );
+        assert_eq!(
+            fractose
+                .cancel_securitize("nft.testnet".parse().unwrap(), "1".to_string())
+                .err(),
+            Some(ContractError::NotFound("securitize request"))
+        );
+
+        context("fractose.testnet");
+        assert_eq!(
+            fractose
+                .abort_orphaned_shares("shares.fractose.testnet".parse().unwrap())
+                .err(),
+            Some(ContractError::NotFound("shares contract"))
+        );
+    }
+
+    #[test]
+    fn invalid_securitize_args_are_rejected() {
+        let owner: AccountId = "alice.testnet".parse().unwrap();
+        let holder = |account: &str, n_shares: u128| ShareAllocation {
+            account_id: account.parse().unwrap(),
+            n_shares: U128(n_shares),
+        };
+        assert_eq!(
+            args(0, 100, vec![]).assert_valid(&owner),
+            Err(ContractError::InvalidArgument("invalid shares count"))
+        );
+        assert_eq!(
+            args(3, 100, vec![]).assert_valid(&owner),
+            Err(ContractError::InvalidArgument(
+                "share price cannot be fractional"
+            ))
+        );
+        assert_eq!(
+            args(10, 100, vec![holder("alice.testnet", 1)]).assert_valid(&owner),
+            Err(ContractError::InvalidArgument(
+                "owner cannot be a share holder"
+            ))
+        );
+        assert_eq!(
+            args(
+                10,
+                100,
+                vec![holder("bob.testnet", 1), holder("bob.testnet", 1)]
+            )
+            .assert_valid(&owner),
+            Err(ContractError::InvalidArgument("duplicate share holder"))
+        );
+        assert_eq!(
+            args(10, 100, vec![holder("bob.testnet", 11)]).assert_valid(&owner),
+            Err(ContractError::InvalidArgument(
+                "allocated shares exceed shares count"
+            ))
+        );
+        assert_eq!(
+            args(10, 100, vec![holder("bob.testnet", 10)]).assert_valid(&owner),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn approval_must_match_the_owners_request() {
+        let nft: AccountId = "nft.testnet".parse().unwrap();
+        let owner: AccountId = "alice.testnet".parse().unwrap();
+        let request = args(10, 100, vec![]);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("fractose.testnet".parse().unwrap())
+            .predecessor_account_id(owner.clone())
+            .attached_deposit(SHARES_ACCOUNT_DEPOSIT)
+            .build());
+        let mut fractose = Fractose {
+            latest_shares_version: 1,
+            ..Default::default()
+        };
+        fractose.shares_code.insert(&1, &vec![0]);
+        fractose
+            .request_securitize(nft.clone(), "1".to_string(), request.clone())
+            .unwrap();
+        assert_eq!(
+            fractose.request_securitize(nft.clone(), "1".to_string(), request.clone()),
+            Err(ContractError::AlreadyExists("securitize request"))
+        );
+
+        // Another contract reporting the same owner and token cannot spend the deposit
+        context("fake-nft.testnet");
+        assert_eq!(
+            fractose
+                .securitize(
+                    "fake-nft.testnet".parse().unwrap(),
+                    "1".to_string(),
+                    owner.clone(),
+                    0,
+                    request.clone()
+                )
+                .err(),
+            Some(ContractError::NotFound("securitize request"))
+        );
+
+        context("nft.testnet");
+        assert_eq!(
+            fractose
+                .securitize(
+                    nft.clone(),
+                    "1".to_string(),
+                    owner.clone(),
+                    0,
+                    args(10, 1000, vec![])
+                )
+                .err(),
+            Some(ContractError::InvalidArgument(
+                "approval does not match the securitize request"
+            ))
+        );
+        assert!(fractose
+            .securitize(nft.clone(), "1".to_string(), owner.clone(), 0, request)
+            .is_ok());
+        assert_eq!(
+            fractose.get_securitize_request(owner, nft.clone(), "1".to_string()),
+            None
+        );
+        assert!(fractose
+            .pending
+            .contains(&get_nft_address(nft, "1".to_string())));
+    }
+
+    fn escrow_callback(
+        fractose: &mut Fractose,
+        token: Result<Option<Token>, PromiseError>,
+        code_version: u32,
+    ) -> bool {
+        fractose.pending.insert(&"nft.testnet/1".to_string());
+        let escrowed = fractose.on_nft_escrowed(
+            token,
+            "nft.testnet".parse().unwrap(),
+            "1".to_string(),
+            "alice.testnet".parse().unwrap(),
+            "nft-testnet-1.fractose.testnet".parse().unwrap(),
+            args(10, 100, vec![]),
+            code_version,
+        );
+        assert!(!fractose.pending.contains(&"nft.testnet/1".to_string()));
+        matches!(escrowed, PromiseOrValue::Value(true))
+    }
+
+    fn token_of(owner: &str) -> Result<Option<Token>, PromiseError> {
+        Ok(Some(Token {
+            token_id: "1".to_string(),
+            owner_id: owner.parse().unwrap(),
+            metadata: None,
+        }))
+    }
+
+    /// Methods called and NEAR transferred by the receipts created for an account
+    fn receipts_to(receiver: &str) -> (Vec<String>, Balance) {
+        let mut calls = vec![];
+        let mut transferred = 0;
+        for receipt in get_created_receipts() {
+            if receipt.receiver_id.as_str() != receiver {
+                continue;
+            }
+            for action in receipt.actions {
+                match action {
+                    VmAction::FunctionCall { function_name, .. } => calls.push(function_name),
+                    VmAction::Transfer { deposit } => transferred += deposit,
+                    _ => {}
+                }
+            }
+        }
+        (calls, transferred)
+    }
+
+    #[test]
+    fn failed_escrow_returns_the_nft_and_the_deposit() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+
+        // The shares code is gone after escrow
+        assert!(!escrow_callback(
+            &mut fractose,
+            token_of("fractose.testnet"),
+            1
+        ));
+        assert_eq!(receipts_to("nft.testnet").0, vec!["nft_transfer"]);
+        assert_eq!(receipts_to("alice.testnet").1, SHARES_ACCOUNT_DEPOSIT);
+
+        // The owner of the token could not be read back
+        context("fractose.testnet");
+        assert!(!escrow_callback(
+            &mut fractose,
+            Err(PromiseError::Failed),
+            1
+        ));
+        assert_eq!(receipts_to("nft.testnet").0, vec!["nft_transfer"]);
+        assert_eq!(receipts_to("alice.testnet").1, SHARES_ACCOUNT_DEPOSIT);
+
+        // The token was never escrowed, only the deposit is refunded
+        context("fractose.testnet");
+        assert!(!escrow_callback(
+            &mut fractose,
+            token_of("alice.testnet"),
+            1
+        ));
+        assert!(receipts_to("nft.testnet").0.is_empty());
+        assert_eq!(receipts_to("alice.testnet").1, SHARES_ACCOUNT_DEPOSIT);
+    }
+
+    #[test]
+    fn escrowed_nft_deploys_the_shares_contract() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+        fractose.shares_code.insert(&1, &vec![0]);
+        fractose.pending.insert(&"nft.testnet/1".to_string());
+        let escrowed = fractose.on_nft_escrowed(
+            token_of("fractose.testnet"),
+            "nft.testnet".parse().unwrap(),
+            "1".to_string(),
+            "alice.testnet".parse().unwrap(),
+            "nft-testnet-1.fractose.testnet".parse().unwrap(),
+            args(10, 100, vec![]),
+            1,
+        );
+        assert!(matches!(escrowed, PromiseOrValue::Promise(_)));
+        // The batch is scheduled when the returned promise is dropped
+        drop(escrowed);
+        assert!(fractose.pending.contains(&"nft.testnet/1".to_string()));
+        assert_eq!(
+            receipts_to("nft-testnet-1.fractose.testnet"),
+            (vec!["create".to_string()], SHARES_ACCOUNT_DEPOSIT)
+        );
+    }
+
+    #[test]
+    fn nft_reference_without_hash_is_not_derived() {
+        context("fractose.testnet");
+        let token = |reference_hash: Option<Base64VecU8>| Token {
+            token_id: "1".to_string(),
+            owner_id: "fractose.testnet".parse().unwrap(),
+            metadata: Some(TokenMetadata {
+                title: Some("Solar farm".to_string()),
+                reference: Some("https://example.com/1.json".to_string()),
+                reference_hash,
+            }),
+        };
+
+        let metadata = args(10, 100, vec![]).share_metadata(&token(None));
+        assert_eq!(metadata.reference, None);
+        assert_eq!(metadata.reference_hash, None);
+        assert_eq!(metadata.symbol, "SOLARFAR");
+
+        let hash = Base64VecU8(vec![1; 32]);
+        let metadata = args(10, 100, vec![]).share_metadata(&token(Some(hash.clone())));
+        assert_eq!(
+            metadata.reference.as_deref(),
+            Some("https://example.com/1.json")
+        );
+        assert_eq!(metadata.reference_hash, Some(hash));
+    }
+
+    fn shares_contract() -> AccountId {
+        "nft-testnet-1.fractose.testnet".parse().unwrap()
+    }
+
+    fn holders() -> Vec<ShareAllocation> {
+        vec![ShareAllocation {
+            account_id: "bob.testnet".parse().unwrap(),
+            n_shares: U128(4),
+        }]
+    }
+
+    fn created_callback(
+        fractose: &mut Fractose,
+        created: Result<(), PromiseError>,
+    ) -> PromiseOrValue<bool> {
+        fractose.pending.insert(&"nft.testnet/1".to_string());
+        fractose.on_shares_created(
+            created,
+            "nft.testnet".parse().unwrap(),
+            "1".to_string(),
+            "alice.testnet".parse().unwrap(),
+            shares_contract(),
+            holders(),
+            1,
+        )
+    }
+
+    fn complete_callback(fractose: &mut Fractose, transferred: Result<(), PromiseError>) -> bool {
+        fractose.pending.insert(&"nft.testnet/1".to_string());
+        fractose.on_securitize_complete(
+            transferred,
+            "nft.testnet".parse().unwrap(),
+            "1".to_string(),
+            "alice.testnet".parse().unwrap(),
+            shares_contract(),
+            holders(),
+            1,
+        )
+    }
+
+    #[test]
+    fn failed_shares_creation_returns_the_nft_and_the_deposit() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+        let created = created_callback(&mut fractose, Err(PromiseError::Failed));
+        assert!(matches!(created, PromiseOrValue::Value(false)));
+        assert!(!fractose.pending.contains(&"nft.testnet/1".to_string()));
+        assert_eq!(receipts_to("nft.testnet").0, vec!["nft_transfer"]);
+        assert_eq!(receipts_to("alice.testnet").1, SHARES_ACCOUNT_DEPOSIT);
+        assert!(fractose.securitized.get(&shares_contract()).is_none());
+    }
+
+    #[test]
+    fn created_shares_contract_receives_the_nft() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+        let created = created_callback(&mut fractose, Ok(()));
+        assert!(matches!(created, PromiseOrValue::Promise(_)));
+        drop(created);
+        assert!(fractose.pending.contains(&"nft.testnet/1".to_string()));
+        assert_eq!(receipts_to("nft.testnet").0, vec!["nft_transfer"]);
+        assert_eq!(receipts_to("alice.testnet").1, 0);
+    }
+
+    #[test]
+    fn transferred_nft_is_registered_with_its_shares_contract() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+        assert!(complete_callback(&mut fractose, Ok(())));
+        assert!(!fractose.pending.contains(&"nft.testnet/1".to_string()));
+        assert_eq!(
+            fractose
+                .nft_to_shares_address
+                .get(&"nft.testnet/1".to_string()),
+            Some(shares_contract())
+        );
+        let entry = fractose.securitized.get(&shares_contract()).unwrap();
+        assert_eq!(entry.status, SharesStatus::Active);
+        assert_eq!(entry.owner_id.as_str(), "alice.testnet");
+        assert_eq!(
+            fractose.allocations.get(&shares_contract()),
+            Some(holders())
+        );
+        assert!(get_created_receipts().is_empty());
+    }
+
+    #[test]
+    fn failed_nft_transfer_deletes_the_orphaned_shares_contract() {
+        context("fractose.testnet");
+        let mut fractose = Fractose::default();
+        assert!(!complete_callback(&mut fractose, Err(PromiseError::Failed)));
+        assert!(!fractose.pending.contains(&"nft.testnet/1".to_string()));
+        assert!(fractose
+            .nft_to_shares_address
+            .get(&"nft.testnet/1".to_string())
+            .is_none());
+        assert_eq!(
+            fractose.securitized.get(&shares_contract()).unwrap().status,
+            SharesStatus::Orphaned
+        );
+        // The deposit is held by the shares contract until it is deleted
+        assert_eq!(receipts_to("nft.testnet").0, vec!["nft_transfer"]);
+        assert_eq!(receipts_to("alice.testnet").1, 0);
+        assert_eq!(
+            receipts_to("nft-testnet-1.fractose.testnet").0,
+            vec!["abort"]
+        );
+
+        // A failed deletion keeps the entry so it can be retried
+        context("fractose.testnet");
+        assert!(!fractose.on_shares_aborted(Err(PromiseError::Failed), shares_contract()));
+        assert!(fractose.securitized.get(&shares_contract()).is_some());
+        assert!(fractose.abort_orphaned_shares(shares_contract()).is_ok());
+
+        context("fractose.testnet");
+        assert!(fractose.on_shares_aborted(Ok(()), shares_contract()));
+        assert!(fractose.securitized.get(&shares_contract()).is_none());
+        assert_eq!(receipts_to("alice.testnet").1, SHARES_ACCOUNT_DEPOSIT);
+    }
+}
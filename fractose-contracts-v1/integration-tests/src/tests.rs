@@ -0,0 +1,105 @@
+use std::{env, fs};
+use near_units::parse_near;
+use serde_json::json;
+use workspaces::{Account, Contract};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let wasm_arg: &str = &(env::args().nth(1).unwrap());
+    let wasm_filepath = fs::canonicalize(env::current_dir()?.join(wasm_arg))?;
+    let relay_arg: &str = &(env::args().nth(2).unwrap());
+    let relay_filepath = fs::canonicalize(env::current_dir()?.join(relay_arg))?;
+
+    let worker = workspaces::sandbox().await?;
+    let wasm = std::fs::read(wasm_filepath)?;
+    let shares = worker.dev_deploy(&wasm).await?;
+    let relay_wasm = std::fs::read(relay_filepath)?;
+    let relay = worker.dev_deploy(&relay_wasm).await?;
+
+    // create accounts
+    let account = worker.dev_create_account().await?;
+    let factory = account
+        .create_subaccount( "factory")
+        .initial_balance(parse_near!("10 N"))
+        .transact()
+        .await?
+        .into_result()?;
+    let alice = account
+        .create_subaccount( "alice")
+        .initial_balance(parse_near!("30 N"))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // alice securitized an NFT and owns every share, so redeeming costs her nothing
+    factory.call(shares.id(), "create")
+        .args_json(json!({
+            "args": {
+                "nft_contract_address": "nft.test.near",
+                "nft_token_id": "token-1",
+                "owner_id": alice.id(),
+                "shares_count": "1000",
+                "decimals": 0,
+                "share_price": "10",
+                "share_holders": [],
+                "n_shares": [],
+                "payment_token": null,
+                "lts_token": null,
+                "token_metadata": {
+                    "name": "Token 1 shares",
+                    "symbol": "TOKEN-1",
+                    "icon": null,
+                    "reference": null,
+                    "reference_hash": null,
+                },
+            },
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // begin tests
+    test_relay_cannot_redeem_for_holder(&alice, &relay, &shares).await?;
+    Ok(())
+}
+
+async fn balance_of(user: &Account, shares: &Contract) -> anyhow::Result<String> {
+    let balance: String = user
+        .call(shares.id(), "ft_balance_of")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?
+        .json()?;
+    Ok(balance)
+}
+
+async fn test_relay_cannot_redeem_for_holder(
+    user: &Account,
+    relay: &Contract,
+    shares: &Contract,
+) -> anyhow::Result<()> {
+    // alice signs the transaction, but the redeem reaches the shares from the relay,
+    // which holds no shares and would have to pay the full exit price
+    let outcome = user
+        .call(relay.id(), "forward")
+        .args_json(json!({
+            "receiver_id": shares.id(),
+            "method_name": "redeem",
+            "args": "{}",
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    assert_eq!(balance_of(user, shares).await?, "1000");
+    let redeem_amount: String = user
+        .call(shares.id(), "redeem_amount_of")
+        .args_json(json!({"from": user.id()}))
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(redeem_amount, "0");
+    println!("      Passed ✅ relay can't redeem for a holder");
+    Ok(())
+}
@@ -0,0 +1,7 @@
+#!/bin/bash
+set -e
+
+# Build the shares and the relay fixture, then run the sandbox tests against them
+(cd ../shares && RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release)
+(cd ../../integration-tests/relay && ./build.sh)
+cargo run --example integration-tests "../shares/target/wasm32-unknown-unknown/release/nft_shares.wasm" "../../integration-tests/relay/res/relay.wasm"
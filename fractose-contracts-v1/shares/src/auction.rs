@@ -0,0 +1,23 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+/// Auctions run for three days after being started
+pub const AUCTION_DURATION: u64 = 3 * 24 * 60 * 60 * 1_000_000_000;
+
+/// English auction for the underlying NFT, bids are escrowed in NEAR
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    pub reserve_price: U128,
+    pub highest_bidder: AccountId,
+    pub highest_bid: U128,
+    pub end_time: U64,
+}
+
+impl Auction {
+    pub fn is_open(&self, now: u64) -> bool {
+        now < self.end_time.0
+    }
+}
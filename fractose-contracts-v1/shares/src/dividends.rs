@@ -0,0 +1,123 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, Balance, IntoStorageKey};
+use lightency_errors::{require, ContractError};
+
+use crate::u256::U256;
+
+/// Scale of the dividend per share accumulator
+const DIVIDEND_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Asset revenue is deposited and paid out in
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DividendAsset {
+    Near,
+    Lts,
+}
+
+/// Unclaimed dividends of an account
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Dividends {
+    pub near: U128,
+    pub lts: U128,
+}
+
+/// Revenue in one asset, split pro-rata per share
+///
+/// Every deposit raises a cumulative dividend per share. An account is owed its
+/// balance times the growth of the accumulator since it was last settled, so
+/// balances must be settled before they change. Settling rounds down, the
+/// remainder is never owed to anyone.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct DividendPool {
+    /// Cumulative dividend per share, scaled by `DIVIDEND_PRECISION`
+    per_share: [u64; 4],
+    /// Accumulator value each account was last settled at
+    settled_per_share: LookupMap<AccountId, [u64; 4]>,
+    /// Settled dividends not claimed yet
+    unclaimed: LookupMap<AccountId, Balance>,
+    /// Settled dividends not claimed yet or whose payout is not confirmed
+    outstanding: Balance,
+}
+
+/// Pool layout before payouts in flight were tracked
+#[derive(BorshDeserialize)]
+pub struct DividendPoolV1 {
+    per_share: [u64; 4],
+    settled_per_share: LookupMap<AccountId, [u64; 4]>,
+    unclaimed: LookupMap<AccountId, Balance>,
+}
+
+/// Dividends settled before the upgrade are not counted as outstanding
+impl From<DividendPoolV1> for DividendPool {
+    fn from(pool: DividendPoolV1) -> Self {
+        let DividendPoolV1 { per_share, settled_per_share, unclaimed } = pool;
+        Self { per_share, settled_per_share, unclaimed, outstanding: 0 }
+    }
+}
+
+impl DividendPool {
+    pub fn new<S: IntoStorageKey>(settled_prefix: S, unclaimed_prefix: S) -> Self {
+        Self {
+            per_share: [0; 4],
+            settled_per_share: LookupMap::new(settled_prefix),
+            unclaimed: LookupMap::new(unclaimed_prefix),
+            outstanding: 0,
+        }
+    }
+
+    /// Split revenue between the current supply of shares
+    pub fn distribute(&mut self, amount: Balance, total_supply: Balance) -> Result<(), ContractError> {
+        require(total_supply > 0, ContractError::InvalidState("no shares to distribute to"))?;
+        let increase = U256::from(amount) * U256::from(DIVIDEND_PRECISION) / U256::from(total_supply);
+        self.per_share = (U256(self.per_share) + increase).0;
+        Ok(())
+    }
+
+    /// Dividends owed to an account holding `balance` shares, settled or not
+    pub fn unclaimed_of(&self, account_id: &AccountId, balance: Balance) -> Balance {
+        self.unclaimed.get(account_id).unwrap_or(0) + self.pending(account_id, balance)
+    }
+
+    /// Move dividends accrued on `balance` to the unclaimed amount of an account
+    pub fn settle(&mut self, account_id: &AccountId, balance: Balance) {
+        let pending = self.pending(account_id, balance);
+        if pending > 0 {
+            self.credit(account_id, pending);
+            self.outstanding += pending;
+        }
+        self.settled_per_share.insert(account_id, &self.per_share);
+    }
+
+    /// Take all unclaimed dividends of a settled account, they stay outstanding until paid
+    pub fn take(&mut self, account_id: &AccountId) -> Balance {
+        self.unclaimed.remove(account_id).unwrap_or(0)
+    }
+
+    /// Confirm the payout of taken dividends
+    /// Saturates for dividends settled before `outstanding` was tracked
+    pub fn paid(&mut self, amount: Balance) {
+        self.outstanding = self.outstanding.saturating_sub(amount);
+    }
+
+    /// Dividends settled to accounts and not paid out yet
+    pub fn outstanding(&self) -> Balance {
+        self.outstanding
+    }
+
+    /// Give back dividends whose payout failed
+    pub fn credit(&mut self, account_id: &AccountId, amount: Balance) {
+        let unclaimed = self.unclaimed.get(account_id).unwrap_or(0) + amount;
+        self.unclaimed.insert(account_id, &unclaimed);
+    }
+
+    fn pending(&self, account_id: &AccountId, balance: Balance) -> Balance {
+        let settled = U256(self.settled_per_share.get(account_id).unwrap_or([0; 4]));
+        let growth = U256(self.per_share) - settled;
+        (U256::from(balance) * growth / U256::from(DIVIDEND_PRECISION)).as_u128()
+    }
+}
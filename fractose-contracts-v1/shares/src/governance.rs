@@ -0,0 +1,46 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+/// Holder proposals are open for votes during seven days
+pub const VOTING_DURATION: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Action executed once holders approve a proposal
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProposalKind {
+    /// Change the share price, and with it the exit price
+    SetSharePrice { share_price: U128 },
+    /// Sell the NFT to a buyer for the amount they escrowed as an offer
+    AcceptOffer { buyer: AccountId },
+}
+
+/// Holder proposal, votes are weighted by share balances at creation
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: AccountId,
+    pub kind: ProposalKind,
+    pub votes_for: U128,
+    pub votes_against: U128,
+    /// Total supply when the proposal was created
+    pub total_supply: U128,
+    pub end_time: U64,
+}
+
+impl Proposal {
+    pub fn is_open(&self, now: u64) -> bool {
+        now < self.end_time.0 && !self.is_approved() && !self.is_rejected()
+    }
+
+    /// Approved once a majority of the snapshotted supply voted for it
+    pub fn is_approved(&self) -> bool {
+        self.votes_for.0 * 2 > self.total_supply.0
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.votes_against.0 * 2 >= self.total_supply.0
+    }
+}
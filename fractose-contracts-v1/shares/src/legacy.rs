@@ -0,0 +1,269 @@
+//! State layouts deployed before the state was tagged with its `StateVersion`
+//!
+//! Each layout converts into the next one, down to the current `Shares`. The layouts
+//! all start with the fungible token, so they are told apart by parsing, newest first.
+use near_contract_standards::fungible_token::FungibleToken;
+use near_sdk::borsh::{self, BorshDeserialize};
+use near_sdk::collections::{LazyOption, LookupMap};
+use near_sdk::{AccountId, Balance};
+
+use crate::auction::Auction;
+use crate::dividends::{DividendPool, DividendPoolV1};
+use crate::governance::Proposal;
+use crate::shares_metadata::SharesMetadata;
+use crate::{Shares, StateVersion, StorageKey};
+
+/// Shares with an auction, upgradable by the factory
+#[derive(BorshDeserialize)]
+struct SharesV1 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    factory: AccountId,
+}
+
+/// Holder governance and buyout offers
+#[derive(BorshDeserialize)]
+struct SharesV2 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    factory: AccountId,
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    votes: LookupMap<AccountId, u64>,
+    offers: LookupMap<AccountId, Balance>,
+}
+
+/// NEAR and LTS dividends
+#[derive(BorshDeserialize)]
+struct SharesV3 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    factory: AccountId,
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    votes: LookupMap<AccountId, u64>,
+    offers: LookupMap<AccountId, Balance>,
+    near_dividends: DividendPoolV1,
+    lts_dividends: DividendPoolV1,
+}
+
+/// Escrowed offers and outstanding dividends keep the account open
+#[derive(BorshDeserialize)]
+struct SharesV4 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    factory: AccountId,
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    votes: LookupMap<AccountId, u64>,
+    offers: LookupMap<AccountId, Balance>,
+    offers_total: Balance,
+    near_dividends: DividendPool,
+    lts_dividends: DividendPool,
+}
+
+/// NFT releases are confirmed in a callback
+#[derive(BorshDeserialize)]
+struct SharesV5 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    releasing: bool,
+    factory: AccountId,
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    votes: LookupMap<AccountId, u64>,
+    offers: LookupMap<AccountId, Balance>,
+    offers_total: Balance,
+    near_dividends: DividendPool,
+    lts_dividends: DividendPool,
+}
+
+/// LTS token passed by the factory
+#[derive(BorshDeserialize)]
+struct SharesV6 {
+    token: FungibleToken,
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    vault: Balance,
+    releasing: bool,
+    factory: AccountId,
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    votes: LookupMap<AccountId, u64>,
+    offers: LookupMap<AccountId, Balance>,
+    offers_total: Balance,
+    near_dividends: DividendPool,
+    lts_dividends: DividendPool,
+    lts_token: Option<AccountId>,
+}
+
+/// Current state from an untagged layout, `None` if no known layout matches
+pub(crate) fn from_untagged(state: &[u8]) -> Option<Shares> {
+    if let Ok(state) = SharesV6::try_from_slice(state) {
+        return Some(state.into());
+    }
+    if let Ok(state) = SharesV5::try_from_slice(state) {
+        return Some(SharesV6::from(state).into());
+    }
+    if let Ok(state) = SharesV4::try_from_slice(state) {
+        return Some(SharesV6::from(SharesV5::from(state)).into());
+    }
+    if let Ok(state) = SharesV3::try_from_slice(state) {
+        return Some(SharesV6::from(SharesV5::from(SharesV4::from(state))).into());
+    }
+    if let Ok(state) = SharesV2::try_from_slice(state) {
+        return Some(SharesV6::from(SharesV5::from(SharesV4::from(state))).into());
+    }
+    SharesV1::try_from_slice(state)
+        .ok()
+        .map(|state| SharesV6::from(SharesV5::from(SharesV4::from(SharesV2::from(state)))).into())
+}
+
+impl From<SharesV1> for SharesV2 {
+    fn from(state: SharesV1) -> Self {
+        let SharesV1 { token, metadata, auction, vault, factory } = state;
+        Self {
+            token,
+            metadata,
+            auction,
+            vault,
+            factory,
+            proposal: LazyOption::new(StorageKey::Proposal, None),
+            proposal_count: 0,
+            snapshots: LookupMap::new(StorageKey::Snapshots),
+            votes: LookupMap::new(StorageKey::Votes),
+            offers: LookupMap::new(StorageKey::Offers),
+        }
+    }
+}
+
+/// The dividend pools are created empty
+impl From<SharesV2> for SharesV4 {
+    fn from(state: SharesV2) -> Self {
+        let SharesV2 { token, metadata, auction, vault, factory, proposal, proposal_count, snapshots, votes, offers } = state;
+        Self {
+            token,
+            metadata,
+            auction,
+            vault,
+            factory,
+            proposal,
+            proposal_count,
+            snapshots,
+            votes,
+            offers,
+            offers_total: 0,
+            near_dividends: DividendPool::new(StorageKey::NearDividendsSettled, StorageKey::NearDividendsUnclaimed),
+            lts_dividends: DividendPool::new(StorageKey::LtsDividendsSettled, StorageKey::LtsDividendsUnclaimed),
+        }
+    }
+}
+
+/// Offers escrowed and dividends settled before their totals were tracked are not counted
+/// in them, they can still be withdrawn and claimed but do not keep the account open
+impl From<SharesV3> for SharesV4 {
+    fn from(state: SharesV3) -> Self {
+        let SharesV3 { token, metadata, auction, vault, factory, proposal, proposal_count, snapshots, votes, offers, near_dividends, lts_dividends } = state;
+        Self {
+            token,
+            metadata,
+            auction,
+            vault,
+            factory,
+            proposal,
+            proposal_count,
+            snapshots,
+            votes,
+            offers,
+            offers_total: 0,
+            near_dividends: near_dividends.into(),
+            lts_dividends: lts_dividends.into(),
+        }
+    }
+}
+
+impl From<SharesV4> for SharesV5 {
+    fn from(state: SharesV4) -> Self {
+        let SharesV4 { token, metadata, auction, vault, factory, proposal, proposal_count, snapshots, votes, offers, offers_total, near_dividends, lts_dividends } = state;
+        Self {
+            token,
+            metadata,
+            auction,
+            vault,
+            releasing: false,
+            factory,
+            proposal,
+            proposal_count,
+            snapshots,
+            votes,
+            offers,
+            offers_total,
+            near_dividends,
+            lts_dividends,
+        }
+    }
+}
+
+impl From<SharesV5> for SharesV6 {
+    fn from(state: SharesV5) -> Self {
+        let SharesV5 { token, metadata, auction, vault, releasing, factory, proposal, proposal_count, snapshots, votes, offers, offers_total, near_dividends, lts_dividends } = state;
+        Self {
+            token,
+            metadata,
+            auction,
+            vault,
+            releasing,
+            factory,
+            proposal,
+            proposal_count,
+            snapshots,
+            votes,
+            offers,
+            offers_total,
+            near_dividends,
+            lts_dividends,
+            lts_token: None,
+        }
+    }
+}
+
+impl From<SharesV6> for Shares {
+    fn from(state: SharesV6) -> Self {
+        let SharesV6 { token, metadata, auction, vault, releasing, factory, proposal, proposal_count, snapshots, votes, offers, offers_total, near_dividends, lts_dividends, lts_token } = state;
+        Self {
+            version: StateVersion::V7,
+            token,
+            metadata,
+            auction,
+            vault,
+            releasing,
+            factory,
+            proposal,
+            proposal_count,
+            snapshots,
+            votes,
+            offers,
+            offers_total,
+            near_dividends,
+            lts_dividends,
+            lts_token,
+            unpaid: LookupMap::new(StorageKey::Unpaid),
+            payouts_outstanding: 0,
+        }
+    }
+}
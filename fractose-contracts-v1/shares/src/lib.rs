@@ -1,43 +1,95 @@
-use std::convert::TryInto;
-
+use fractose_common::{ext_factory, CreateSharesArgs, FractoseShares, TokenId};
+use lightency_errors::{require, ContractError};
 use near_contract_standards::fungible_token::FungibleToken;
+use near_contract_standards::fungible_token::core::{ext_ft_core, FungibleTokenCore};
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
 use near_sdk::{
-    env, AccountId, Balance, PromiseOrValue, Promise,
-    BorshStorageKey, PanicOnDefault, log,
+    env, AccountId, Balance, Gas, PromiseError, PromiseOrValue, PromiseResult, Promise,
+    BorshStorageKey, FunctionError, PanicOnDefault, log,
     near_bindgen, ext_contract,
-    collections::LazyOption,
-    json_types::{ValidAccountId, U64, U128},
+    collections::{LazyOption, LookupMap},
+    json_types::{U64, U128},
     borsh::{self, BorshDeserialize, BorshSerialize}
 };
 
+mod auction;
+mod dividends;
+mod governance;
+mod legacy;
 mod shares_metadata;
+mod u256;
+use auction::{Auction, AUCTION_DURATION};
+use dividends::{DividendAsset, DividendPool, Dividends};
+use governance::{Proposal, ProposalKind, VOTING_DURATION};
 use shares_metadata::{SharesMetadata, SharesMetadataProvider, SHARES_FT_METADATA_SPEC};
+use u256::U256;
 
-near_sdk::setup_alloc!();
-
-pub type TokenId = String;
-
-#[ext_contract]
+#[ext_contract(ext_nft_core)]
 pub trait NonFungibleTokenCore {
     fn nft_transfer(
         &mut self,
-        receiver_id: ValidAccountId,
+        receiver_id: AccountId,
         token_id: TokenId,
-        approval_id: Option<U64>,
+        approval_id: Option<u64>,
         memo: Option<String>,
     );
 }
 
-#[ext_contract]
-pub trait Shares {
-    fn cleanup(&mut self);
-}
+const TGAS: u64 = 1_000_000_000_000;
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10 * TGAS);
+const GAS_FOR_REPORT: Gas = Gas(5 * TGAS);
+const GAS_FOR_UPGRADE_SHARES: Gas = Gas(150 * TGAS);
+const GAS_FOR_MIGRATE: Gas = Gas(20 * TGAS);
+const GAS_FOR_NFT_TRANSFER: Gas = Gas(30 * TGAS);
+const GAS_FOR_ON_PAID: Gas = Gas(20 * TGAS);
+const GAS_FOR_ON_DIVIDENDS_PAID: Gas = Gas(5 * TGAS);
+const GAS_FOR_ON_NFT_RELEASED: Gas = Gas(40 * TGAS);
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Shares {
+    /// Layout of this state, kept first so that `migrate` can read it before the rest
+    version: StateVersion,
     token: FungibleToken,
-    metadata: LazyOption<SharesMetadata>
+    metadata: LazyOption<SharesMetadata>,
+    auction: LazyOption<Auction>,
+    /// Near tokens left for shareholders to claim once the NFT is released
+    vault: Balance,
+    /// The NFT is being transferred out, claims wait until the transfer is confirmed
+    releasing: bool,
+    /// Fractose factory that deployed this contract, notified of redeem and close, receives leftover NEAR on deletion
+    factory: AccountId,
+    /// Holder proposal to change the share price or accept an offer
+    proposal: LazyOption<Proposal>,
+    proposal_count: u64,
+    /// Balance of an account when the proposal with the given id was created,
+    /// recorded the first time the balance changes while the proposal is open
+    snapshots: LookupMap<AccountId, (u64, Balance)>,
+    /// Id of the last proposal an account voted on
+    votes: LookupMap<AccountId, u64>,
+    /// Payments escrowed by buyers offering to buy the NFT
+    offers: LookupMap<AccountId, Balance>,
+    /// Sum of escrowed offers, the account is kept open until they are withdrawn
+    offers_total: Balance,
+    /// Revenue deposited for holders in NEAR and LTS
+    near_dividends: DividendPool,
+    lts_dividends: DividendPool,
+    /// LTS token accepted as revenue next to NEAR, set by the factory at creation
+    lts_token: Option<AccountId>,
+    /// Payouts of vault shares, offers and outbid bids that failed, withdrawn by their receivers
+    unpaid: LookupMap<AccountId, Balance>,
+    /// Payouts not confirmed yet plus failed ones, the account is kept open until they are paid
+    payouts_outstanding: Balance
+}
+
+/// Layout of the contract state
+///
+/// States from before the tag start with the fungible token, `legacy` reads them.
+/// Add a variant when the layout changes and convert the previous one in `migrate`.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Debug)]
+enum StateVersion {
+    /// Layout after the six untagged ones, with failed payouts credited back
+    V7,
 }
 
 const DATA_IMAGE_SVG_NEAR_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 288 288'%3E%3Cg id='l' data-name='l'%3E%3Cpath d='M187.58,79.81l-30.1,44.69a3.2,3.2,0,0,0,4.75,4.2L191.86,103a1.2,1.2,0,0,1,2,.91v80.46a1.2,1.2,0,0,1-2.12.77L102.18,77.93A15.35,15.35,0,0,0,90.47,72.5H87.34A15.34,15.34,0,0,0,72,87.84V201.16A15.34,15.34,0,0,0,87.34,216.5h0a15.35,15.35,0,0,0,13.08-7.31l30.1-44.69a3.2,3.2,0,0,0-4.75-4.2L96.14,186a1.2,1.2,0,0,1-2-.91V104.61a1.2,1.2,0,0,1,2.12-.77l89.55,107.23a15.35,15.35,0,0,0,11.71,5.43h3.13A15.34,15.34,0,0,0,216,201.16V87.84A15.34,15.34,0,0,0,200.66,72.5h0A15.35,15.35,0,0,0,187.58,79.81Z'/%3E%3C/g%3E%3C/svg%3E";
@@ -46,76 +98,163 @@ const DATA_IMAGE_SVG_NEAR_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://
 enum StorageKey {
     FungibleToken,
     Metadata,
+    Auction,
+    Proposal,
+    Snapshots,
+    Votes,
+    Offers,
+    NearDividendsSettled,
+    NearDividendsUnclaimed,
+    LtsDividendsSettled,
+    LtsDividendsUnclaimed,
+    Unpaid,
 }
 
 #[near_bindgen]
-
-impl Shares {
+impl FractoseShares for Shares {
     #[init]
-    pub fn create(nft_contract_address: AccountId, nft_token_id: TokenId, owner_id: ValidAccountId, shares_count: U128, decimals: u8, share_price: U128, share_holders: Vec<ValidAccountId>, n_shares: Vec<U128>) -> Self {
-        // TODO allow payment in NEP-141 fungible tokens
-        assert!(!env::state_exists(), "Already initialized");
+    #[handle_result]
+    fn create(args: CreateSharesArgs) -> Result<Self, ContractError> {
+        let CreateSharesArgs { nft_contract_address, nft_token_id, owner_id, shares_count, decimals, share_price, share_holders, n_shares, payment_token, lts_token, token_metadata } = args;
+
+        // Exit price and vault are in NEAR unless a NEP-141 payment token is given
+        require(payment_token.as_ref() != Some(&env::current_account_id()), ContractError::InvalidArgument("shares cannot pay for themselves"))?;
 
         let metadata = SharesMetadata {
             spec: SHARES_FT_METADATA_SPEC.to_string(),
-            name: "Example NEAR fungible token".to_string(),
-            symbol: "EXAMPLE".to_string(),
-            icon: Some(DATA_IMAGE_SVG_NEAR_ICON.to_string()),
-            reference: None,
-            reference_hash: None,
+            name: token_metadata.name,
+            symbol: token_metadata.symbol,
+            icon: token_metadata.icon.or_else(|| Some(DATA_IMAGE_SVG_NEAR_ICON.to_string())),
+            reference: token_metadata.reference,
+            reference_hash: token_metadata.reference_hash,
             decimals,
 
             // Shares FT specific metadata
             nft_contract_address: nft_contract_address.clone(),
             nft_token_id: nft_token_id.clone(),
             share_price,
-            released: false
+            released: false,
+            payment_token
         };
-        metadata.assert_valid();
+        metadata.assert_valid()?;
 
         let mut this = Self {
+            version: StateVersion::V7,
             token: FungibleToken::new(StorageKey::FungibleToken),
             metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            auction: LazyOption::new(StorageKey::Auction, None),
+            vault: 0,
+            releasing: false,
+            factory: env::predecessor_account_id(),
+            proposal: LazyOption::new(StorageKey::Proposal, None),
+            proposal_count: 0,
+            snapshots: LookupMap::new(StorageKey::Snapshots),
+            votes: LookupMap::new(StorageKey::Votes),
+            offers: LookupMap::new(StorageKey::Offers),
+            offers_total: 0,
+            near_dividends: DividendPool::new(StorageKey::NearDividendsSettled, StorageKey::NearDividendsUnclaimed),
+            lts_dividends: DividendPool::new(StorageKey::LtsDividendsSettled, StorageKey::LtsDividendsUnclaimed),
+            lts_token,
+            unpaid: LookupMap::new(StorageKey::Unpaid),
+            payouts_outstanding: 0,
         };
         
-        this.token.internal_register_account(&owner_id.clone().try_into().unwrap());
-        this.token.internal_deposit(&owner_id.clone().try_into().unwrap(), shares_count.0);
-        
-        for i in 0..share_holders.len(){
-        this.token.internal_register_account(&share_holders[i].clone().try_into().unwrap());
-        this.token.internal_transfer(&owner_id.clone().try_into().unwrap(),&share_holders[i].clone().try_into().unwrap(), n_shares[i].0, Some("".to_string()));
+        require(share_holders.len() == n_shares.len(), ContractError::InvalidArgument("share holders and n_shares differ in length"))?;
+        let allocated = n_shares.iter().try_fold(0u128, |total, n| total.checked_add(n.0)).ok_or(ContractError::InvalidArgument("allocation overflow"))?;
+        require(allocated <= shares_count.0, ContractError::InvalidArgument("allocated shares exceed shares count"))?;
+
+        this.token.internal_register_account(&owner_id);
+        this.token.internal_deposit(&owner_id, shares_count.0);
+
+        for (holder, n) in share_holders.iter().zip(n_shares.iter()) {
+            this.token.internal_register_account(holder);
+            this.token.internal_transfer(&owner_id, holder, n.0, None);
         }
-        
 
         // Emit event
-        this.on_securitize(owner_id.to_string(), nft_contract_address, nft_token_id);
+        this.on_securitize(owner_id, nft_contract_address, nft_token_id);
+
+        Ok(this)
+    }
+
+    /// Delete the account, the NFT never backed these shares. Leftover NEAR goes to the factory
+    #[handle_result]
+    fn abort(&mut self) -> Result<Promise, ContractError> {
+        require(env::predecessor_account_id() == self.factory, ContractError::Unauthorized("factory"))?;
+        Ok(Promise::new(env::current_account_id()).delete_account(self.factory.clone()))
+    }
+}
 
-        this
+#[near_bindgen]
+impl Shares {
+    /// Migrate state after the factory deployed new code
+    #[init(ignore_state)]
+    #[private]
+    #[handle_result]
+    pub fn migrate() -> Result<Self, ContractError> {
+        let state = env::storage_read(b"STATE").ok_or(ContractError::NotFound("state"))?;
+
+        if let Ok(StateVersion::V7) = StateVersion::deserialize(&mut state.as_slice()) {
+            if let Ok(state) = Self::try_from_slice(&state) {
+                return Ok(state);
+            }
+        }
+
+        // Untagged states start with the length of the token prefix, which may read as a version
+        legacy::from_untagged(&state).ok_or(ContractError::InvalidState("unknown state layout"))
+    }
+
+    /// Deploy the code passed as raw input by the Fractose factory, then migrate
+    #[handle_result]
+    pub fn upgrade(&self) -> Result<Promise, ContractError> {
+        require(env::predecessor_account_id() == self.factory, ContractError::Unauthorized("factory"))?;
+        let code = env::input().ok_or(ContractError::InvalidArgument("missing code"))?;
+
+        Ok(Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), b"{}".to_vec(), 0, GAS_FOR_MIGRATE))
+    }
+
+    /// Ask the factory to upgrade this contract to a stored code version
+    /// Holders approve by holding a majority of shares
+    #[handle_result]
+    pub fn request_upgrade(&mut self, version: u32) -> Result<Promise, ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
+
+        let holder = env::predecessor_account_id();
+        let holder_shares = self.token.accounts.get(&holder).unwrap_or(0);
+        require(holder_shares * 2 > self.token.total_supply, ContractError::Unauthorized("holder of a majority of shares"))?;
+
+        Ok(ext_factory::ext(self.factory.clone())
+            .with_static_gas(GAS_FOR_UPGRADE_SHARES)
+            .upgrade_shares(env::current_account_id(), version))
     }
 
-    /// Exit price in Near to redeem underlying NFT
+    /// Exit price in NEAR or the payment token to redeem underlying NFT
     pub fn exit_price(&self) -> U128 {
         (self.ft_total_supply().0 * self.ft_metadata().share_price.0).into()
     }
 
-    /// Near tokens required by a user in addition to held shares to redeem NFT
-    pub fn redeem_amount_of(&self, from: ValidAccountId) -> U128 {
+    /// Payment tokens required by a user in addition to held shares to redeem NFT
+    #[handle_result]
+    pub fn redeem_amount_of(&self, from: AccountId) -> Result<U128, ContractError> {
         let SharesMetadata { released, share_price, .. } = self.ft_metadata();
-        assert!(!released, "token already redeemed");
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
 
         let user_shares = self.ft_balance_of(from);
 
-        (self.exit_price().0 - user_shares.0 * share_price.0).into()
+        Ok((self.exit_price().0 - user_shares.0 * share_price.0).into())
     }
 
     /// Returns balance Near tokens in vault
     /// NFTs can be redeemed by paying Near. These tokens are the new backing for shares
     pub fn vault_balance(&self) -> U128 {
-        let SharesMetadata { released, share_price, .. } = self.ft_metadata();
+        let SharesMetadata { released, .. } = self.ft_metadata();
         let balance = if !released {
             0
         } else {
-            self.ft_total_supply().0 * share_price.0
+            self.vault
         };
 
         balance.into()
@@ -123,13 +262,15 @@ impl Shares {
 
     /// Once NFT is redeemed by paying exit price, remaining shareholders get a
     /// share of the deposited Near tokens in proportion of their owned shares
-    pub fn vault_balance_of(&self, from: ValidAccountId) -> U128 {
-        let SharesMetadata { released, share_price, .. } = self.ft_metadata();
-        let balance = if !released {
+    pub fn vault_balance_of(&self, from: AccountId) -> U128 {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        let total_supply = self.ft_total_supply().0;
+        let balance = if !released || total_supply == 0 {
             0
         } else {
+            // Yocto amounts times 24 decimals shares overflow u128
             let user_shares = self.ft_balance_of(from);
-            user_shares.0 * share_price.0
+            (U256::from(self.vault) * U256::from(user_shares.0) / U256::from(total_supply)).as_u128()
         };
 
         balance.into()
@@ -137,25 +278,430 @@ impl Shares {
 
     /// Redeem NFT through owned shares or NEAR payment
     #[payable]
-    pub fn redeem(&mut self) {
-        let SharesMetadata { released, nft_token_id, nft_contract_address, .. } = self.ft_metadata();
-        assert!(!released, "token already redeemed");
+    #[handle_result]
+    pub fn redeem(&mut self) -> Result<(), ContractError> {
+        self.assert_paid_in_near()?;
+
+        let user_account = env::predecessor_account_id();
+        let change_amount = self.internal_redeem(user_account.clone(), env::attached_deposit())?;
+
+        // Return change amount to redeemer
+        if change_amount > 0 {
+            Promise::new(user_account).transfer(change_amount);
+        }
+        Ok(())
+    }
+
+    /// Pay with the NEP-141 payment token. `msg` selects the action to pay for:
+    /// `redeem`, `start_auction`, `bid` or `offer`. LTS sent with `revenue` is split between holders. Change is returned to the sender by the token contract
+    ///
+    /// NEP-141 fixes the return type, errors panic with their code instead of being returned
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        match self.internal_on_transfer(sender_id, amount.0, msg) {
+            Ok(unused_amount) => PromiseOrValue::Value(unused_amount.into()),
+            Err(error) => error.panic(),
+        }
+    }
+
+    /// Current buyout auction, if one has been started
+    pub fn get_auction(&self) -> Option<Auction> {
+        self.auction.get()
+    }
+
+    /// Start an English auction for the underlying NFT
+    ///
+    /// The attached deposit is the opening bid and must cover the reserve price,
+    /// which is the exit price derived from share price.
+    #[payable]
+    #[handle_result]
+    pub fn start_auction(&mut self) -> Result<(), ContractError> {
+        self.assert_paid_in_near()?;
+        self.internal_start_auction(env::predecessor_account_id(), env::attached_deposit())
+    }
+
+    /// Outbid the current highest bidder, who gets their bid refunded
+    #[payable]
+    #[handle_result]
+    pub fn bid(&mut self) -> Result<(), ContractError> {
+        self.assert_paid_in_near()?;
+        self.internal_bid(env::predecessor_account_id(), env::attached_deposit())
+    }
 
-        let user_account = env::signer_account_id();
+    /// Release the NFT to the auction winner once the auction has ended
+    ///
+    /// The winning bid goes to the vault, shareholders claim it pro-rata.
+    #[handle_result]
+    pub fn end_auction(&mut self) -> Result<(), ContractError> {
+        let auction = self.auction.get().ok_or(ContractError::NotFound("auction"))?;
+        require(!auction.is_open(env::block_timestamp()), ContractError::TooEarly)?;
 
-        let user_account_object: ValidAccountId = (user_account.clone()).try_into().unwrap();
+        self.auction.remove();
+        self.internal_sell(auction.highest_bidder, auction.highest_bid.0);
+        Ok(())
+    }
 
-        let payment_amount = env::attached_deposit();
-        let redeem_amount = self.redeem_amount_of(user_account_object.clone()).0;
+    /// Current holder proposal, if any
+    pub fn get_proposal(&self) -> Option<Proposal> {
+        self.proposal.get()
+    }
 
-        // TODO allow payment in NEP-141 fungible tokens
-        assert!(payment_amount >= redeem_amount, "insufficient payment amount");
+    /// Shares an account can vote with on the current proposal
+    pub fn voting_power_of(&self, account_id: AccountId) -> U128 {
+        let power = match self.proposal.get() {
+            Some(proposal) => self.voting_power(&proposal, &account_id),
+            None => 0,
+        };
 
-        // Return change amount to redeemer
-        let change_amount = payment_amount - redeem_amount;
-        Promise::new(user_account.clone()).transfer(
-            change_amount
-        );
+        power.into()
+    }
+
+    /// Payment escrowed by a buyer offering to buy the NFT
+    pub fn get_offer(&self, buyer: AccountId) -> U128 {
+        self.offers.get(&buyer).unwrap_or(0).into()
+    }
+
+    /// Offer to buy the NFT for the attached deposit, holders accept it through a proposal
+    #[payable]
+    #[handle_result]
+    pub fn make_offer(&mut self) -> Result<(), ContractError> {
+        self.assert_paid_in_near()?;
+        self.internal_make_offer(env::predecessor_account_id(), env::attached_deposit())
+    }
+
+    /// Get back an escrowed offer, unless holders are voting on it or accepted it
+    /// Once the NFT is released every offer can be withdrawn
+    #[handle_result]
+    pub fn withdraw_offer(&mut self) -> Result<Promise, ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        let buyer = env::predecessor_account_id();
+        if let Some(proposal) = self.proposal.get() {
+            if let ProposalKind::AcceptOffer { buyer: offer_buyer } = &proposal.kind {
+                let pending = proposal.is_open(env::block_timestamp()) || proposal.is_approved();
+                require(released || !(pending && offer_buyer == &buyer), ContractError::InvalidState("offer is under vote"))?;
+            }
+        }
+
+        let amount = self.offers.remove(&buyer).ok_or(ContractError::NotFound("offer"))?;
+        // Offers escrowed before the total was tracked are not counted in it
+        self.offers_total = self.offers_total.saturating_sub(amount);
+
+        // Close the contract once paid if this was the last thing keeping it open
+        Ok(self.pay_out(buyer, amount))
+    }
+
+    /// Payouts to an account that failed and can be withdrawn
+    pub fn get_unpaid(&self, account_id: AccountId) -> U128 {
+        self.unpaid.get(&account_id).unwrap_or(0).into()
+    }
+
+    /// Retry the payouts to the caller that failed, such as a claim before registering
+    /// with the payment token
+    #[handle_result]
+    pub fn withdraw_unpaid(&mut self) -> Result<Promise, ContractError> {
+        let account_id = env::predecessor_account_id();
+        let amount = self.unpaid.remove(&account_id).ok_or(ContractError::NotFound("unpaid payout"))?;
+        // `pay_out` counts the amount as outstanding again
+        self.payouts_outstanding -= amount;
+        Ok(self.pay_out(account_id, amount))
+    }
+
+    /// Propose a new share price or accepting a buyer's offer, replacing any closed proposal
+    /// An approved proposal has to be executed before it can be replaced
+    /// Votes are weighted by share balances at creation
+    #[handle_result]
+    pub fn create_proposal(&mut self, kind: ProposalKind) -> Result<u64, ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
+
+        let now = env::block_timestamp();
+        if let Some(proposal) = self.proposal.get() {
+            require(!proposal.is_open(now), ContractError::InvalidState("a proposal is already open"))?;
+            require(!proposal.is_approved(), ContractError::InvalidState("approved proposal is not executed"))?;
+        }
+
+        let proposer = env::predecessor_account_id();
+        require(self.token.accounts.get(&proposer).unwrap_or(0) > 0, ContractError::Unauthorized("share holders"))?;
+
+        match &kind {
+            ProposalKind::SetSharePrice { share_price } => require(share_price.0 > 0, ContractError::InvalidArgument("share price must be positive"))?,
+            ProposalKind::AcceptOffer { buyer } => require(self.offers.get(buyer).unwrap_or(0) > 0, ContractError::NotFound("offer"))?,
+        }
+
+        self.proposal_count += 1;
+        let proposal = Proposal {
+            id: self.proposal_count,
+            proposer: proposer.clone(),
+            kind,
+            votes_for: 0.into(),
+            votes_against: 0.into(),
+            total_supply: self.token.total_supply.into(),
+            end_time: (now + VOTING_DURATION).into(),
+        };
+        self.proposal.set(&proposal);
+
+        // Emit event
+        self.on_proposal(proposer, proposal.id);
+
+        Ok(proposal.id)
+    }
+
+    /// Vote on the current proposal with the shares held when it was created
+    #[handle_result]
+    pub fn vote(&mut self, approve: bool) -> Result<(), ContractError> {
+        let mut proposal = self.proposal.get().ok_or(ContractError::NotFound("proposal"))?;
+        require(proposal.is_open(env::block_timestamp()), ContractError::VotingClosed)?;
+
+        let voter = env::predecessor_account_id();
+        require(self.votes.get(&voter) != Some(proposal.id), ContractError::AlreadyVoted)?;
+
+        let power = self.voting_power(&proposal, &voter);
+        require(power > 0, ContractError::Unauthorized("share holders at proposal creation"))?;
+
+        if approve {
+            proposal.votes_for = (proposal.votes_for.0 + power).into();
+        } else {
+            proposal.votes_against = (proposal.votes_against.0 + power).into();
+        }
+        self.votes.insert(&voter, &proposal.id);
+        self.proposal.set(&proposal);
+
+        // Emit event
+        self.on_vote(voter, proposal.id, approve, power);
+        Ok(())
+    }
+
+    /// Apply an approved proposal, callable by anyone
+    #[handle_result]
+    pub fn execute_proposal(&mut self) -> Result<(), ContractError> {
+        let proposal = self.proposal.get().ok_or(ContractError::NotFound("proposal"))?;
+        require(proposal.is_approved(), ContractError::NotApproved)?;
+
+        let mut metadata = self.ft_metadata();
+        require(!metadata.released, ContractError::InvalidState("token already redeemed"))?;
+
+        self.proposal.remove();
+        match proposal.kind {
+            ProposalKind::SetSharePrice { share_price } => {
+                metadata.share_price = share_price;
+                self.metadata.replace(&metadata);
+            }
+            ProposalKind::AcceptOffer { buyer } => {
+                require(self.auction.get().is_none(), ContractError::InvalidState("token is being auctioned"))?;
+                let amount = self.offers.remove(&buyer).ok_or(ContractError::NotFound("offer"))?;
+                self.offers_total = self.offers_total.saturating_sub(amount);
+                self.internal_sell(buyer, amount);
+            }
+        }
+
+        // Emit event
+        self.on_proposal_executed(proposal.id);
+        Ok(())
+    }
+
+    /// Once NFT is redeemed, remaining shareholders can claim their share of the vault
+    ///
+    /// Shares are burnt even when their part of the vault rounds down to zero, so the
+    /// supply always reaches zero. The last claimant holds the whole supply and gets
+    /// what is left of the vault, rounding remainders included.
+    #[handle_result]
+    pub fn claim(&mut self) -> Result<(), ContractError> {
+        let SharesMetadata { released,  nft_contract_address, nft_token_id, .. } = self.ft_metadata();
+        require(released, ContractError::InvalidState("token not redeemed"))?;
+        require(!self.releasing, ContractError::ExecutionInProgress)?;
+
+        let user_account = env::predecessor_account_id();
+
+        let user_shares = self.ft_balance_of(user_account.clone());
+        require(user_shares.0 > 0, ContractError::InsufficientBalance)?;
+
+        let claim_amount = self.vault_balance_of(user_account.clone()).0;
+
+        // Burn shares and unregister the claimant
+        self.internal_burn_all(&user_account);
+        self.vault -= claim_amount;
+
+        // Emit event
+        self.on_claim(user_account.clone(), nft_contract_address, nft_token_id, user_shares);
+
+        if claim_amount == 0 {
+            self.internal_finalize();
+            return Ok(());
+        }
+
+        // Pay out the vault share in NEAR or the payment token, then close if this was the last claim
+        self.pay_out(user_account, claim_amount);
+        Ok(())
+    }
+
+    /// Deposit the attached NEAR as revenue, split pro-rata between current holders
+    #[payable]
+    #[handle_result]
+    pub fn deposit_revenue(&mut self) -> Result<(), ContractError> {
+        self.internal_deposit_revenue(DividendAsset::Near, env::predecessor_account_id(), env::attached_deposit())
+    }
+
+    /// Dividends an account can claim in NEAR and LTS
+    pub fn unclaimed_dividends_of(&self, account_id: AccountId) -> Dividends {
+        let balance = self.token.accounts.get(&account_id).unwrap_or(0);
+
+        Dividends {
+            near: self.near_dividends.unclaimed_of(&account_id, balance).into(),
+            lts: self.lts_dividends.unclaimed_of(&account_id, balance).into(),
+        }
+    }
+
+    /// Pay out the caller's dividends in NEAR and LTS
+    pub fn claim_dividends(&mut self) {
+        let account_id = env::predecessor_account_id();
+        self.before_balance_change(&account_id);
+        self.internal_pay_dividends(&account_id);
+    }
+
+    /// Confirm the NFT left for its buyer or redeemer, or restore the shares and refund the payment
+    /// Shares burnt by a redeemer are minted back if the transfer failed
+    #[private]
+    pub fn on_nft_released(&mut self, #[callback_result] transferred: Result<(), PromiseError>, receiver_id: AccountId, refund: U128, shares: U128) {
+        self.releasing = false;
+        let mut metadata = self.ft_metadata();
+
+        if transferred.is_err() {
+            metadata.set_as_unreleased();
+            self.metadata.replace(&metadata);
+            self.vault = 0;
+            if shares.0 > 0 {
+                self.before_balance_change(&receiver_id);
+                if !self.token.accounts.contains_key(&receiver_id) {
+                    self.token.internal_register_account(&receiver_id);
+                }
+                self.token.internal_deposit(&receiver_id, shares.0);
+            }
+            if refund.0 > 0 {
+                self.pay_out(receiver_id.clone(), refund.0);
+            }
+            log!("Token {} could not be released to @{}, {} refunded", metadata.nft_token_id, receiver_id, refund.0);
+            return;
+        }
+
+        // Emit event
+        self.on_redeem(receiver_id, metadata.nft_contract_address, metadata.nft_token_id);
+        self.report_redeemed();
+        self.internal_finalize();
+    }
+
+    /// Confirm a payout, or credit it back to its receiver if the transfer failed
+    #[private]
+    pub fn on_paid(&mut self, receiver_id: AccountId, amount: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            self.payouts_outstanding -= amount.0;
+
+            // The last payout may be all that kept the contract open
+            self.internal_finalize();
+            return;
+        }
+
+        let unpaid = self.unpaid.get(&receiver_id).unwrap_or(0) + amount.0;
+        self.unpaid.insert(&receiver_id, &unpaid);
+        log!("Payout to @{} failed, {} credited back", receiver_id, amount.0);
+    }
+
+    #[private]
+    pub fn on_dividends_paid(&mut self, account_id: AccountId, asset: DividendAsset, amount: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            match asset {
+                DividendAsset::Near => self.near_dividends.paid(amount.0),
+                DividendAsset::Lts => self.lts_dividends.paid(amount.0),
+            }
+
+            // The last payout may be all that kept the contract open
+            self.internal_finalize();
+            return;
+        }
+
+        match asset {
+            DividendAsset::Near => self.near_dividends.credit(&account_id, amount.0),
+            DividendAsset::Lts => self.lts_dividends.credit(&account_id, amount.0),
+        }
+        log!("Dividends payout to @{} failed, {} credited back", account_id, amount.0);
+    }
+}
+
+impl Shares {
+    /// Delete the account if the NFT is released and nothing is left to pay out:
+    /// no shares, vault, escrowed offers, outstanding payouts or dividends
+    ///
+    /// Leftover NEAR, including dividend rounding remainders, goes to the Fractose
+    /// factory that funded the account.
+    fn internal_finalize(&mut self) {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        if !released || self.releasing || self.token.total_supply > 0 || self.vault > 0 || self.offers_total > 0 || self.payouts_outstanding > 0 {
+            return;
+        }
+        if self.near_dividends.outstanding() > 0 || self.lts_dividends.outstanding() > 0 {
+            return;
+        }
+
+        // Mark as closed in the Fractose registry
+        ext_factory::ext(self.factory.clone())
+            .with_static_gas(GAS_FOR_REPORT)
+            .report_closed();
+
+        Promise::new(env::current_account_id()).delete_account(self.factory.clone());
+    }
+
+    fn assert_paid_in_near(&self) -> Result<(), ContractError> {
+        require(self.ft_metadata().payment_token.is_none(), ContractError::InvalidArgument("pay by transferring the payment token"))
+    }
+
+    /// Payment in the NEP-141 payment token, or LTS revenue. Returns the unused amount
+    fn internal_on_transfer(&mut self, sender: AccountId, amount: Balance, msg: String) -> Result<Balance, ContractError> {
+        // LTS revenue is accepted whatever the payment token is
+        if msg == "revenue" {
+            require(self.lts_token.as_ref() == Some(&env::predecessor_account_id()), ContractError::InvalidArgument("revenue is paid in NEAR or LTS"))?;
+            self.internal_deposit_revenue(DividendAsset::Lts, sender, amount)?;
+            return Ok(0);
+        }
+
+        let payment_token = self.ft_metadata().payment_token.ok_or(ContractError::InvalidArgument("shares are paid in NEAR"))?;
+        require(env::predecessor_account_id() == payment_token, ContractError::InvalidArgument("unsupported payment token"))?;
+
+        match msg.as_str() {
+            "redeem" => self.internal_redeem(sender, amount),
+            "start_auction" => self.internal_start_auction(sender, amount).map(|_| 0),
+            "bid" => self.internal_bid(sender, amount).map(|_| 0),
+            "offer" => self.internal_make_offer(sender, amount).map(|_| 0),
+            _ => Err(ContractError::InvalidArgument("unknown payment action")),
+        }
+    }
+
+    /// Pay out of the vault, crediting the amount back to the receiver if the transfer fails
+    fn pay_out(&mut self, receiver_id: AccountId, amount: Balance) -> Promise {
+        self.payouts_outstanding += amount;
+        self.pay(receiver_id.clone(), amount).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_ON_PAID)
+                .on_paid(receiver_id, amount.into())
+        )
+    }
+
+    /// Transfer NEAR or the NEP-141 payment token out of the vault
+    fn pay(&self, receiver_id: AccountId, amount: Balance) -> Promise {
+        match self.ft_metadata().payment_token {
+            Some(payment_token) => ext_ft_core::ext(payment_token)
+                .with_attached_deposit(1)
+                .with_static_gas(GAS_FOR_FT_TRANSFER)
+                .ft_transfer(receiver_id, amount.into(), None),
+            None => Promise::new(receiver_id).transfer(amount),
+        }
+    }
+
+    /// Burn the redeemer's shares and release the NFT, returns the unused payment
+    fn internal_redeem(&mut self, user_account: AccountId, payment_amount: Balance) -> Result<Balance, ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
+        require(self.auction.get().is_none(), ContractError::InvalidState("token is being auctioned"))?;
+
+        let redeem_amount = self.redeem_amount_of(user_account.clone())?.0;
+        require(payment_amount >= redeem_amount, ContractError::InsufficientDeposit)?;
 
         // Set as redeemed
         let mut new_metadata = self.ft_metadata();
@@ -163,80 +709,208 @@ impl Shares {
 
         self.metadata.replace(&new_metadata);
 
-        // Burn shares
-        let user_shares = self.ft_balance_of(user_account_object.clone());
-        self.token.accounts.insert(&user_account, &0);
-        self.token.total_supply -= user_shares.0;
-        self.on_tokens_burned(user_account.clone(), user_shares.0);
-
-        // Transfer NFT to redeemer
-        non_fungible_token_core::nft_transfer(
-            user_account_object.clone(),
-            nft_token_id.clone(),
-            None,
-            None,
-            &nft_contract_address,
-            1,
-            env::prepaid_gas() / 2
-        );
+        // Burn shares and unregister the redeemer
+        let shares = self.internal_burn_all(&user_account);
+
+        // Payment backs the remaining shares
+        self.vault = redeem_amount;
+
+        // Transfer NFT to redeemer, close the contract if no shares are left
+        // The shares and payment are given back if the transfer fails
+        self.release_nft(user_account, redeem_amount, shares);
+
+        Ok(payment_amount - redeem_amount)
+    }
+
+    fn internal_start_auction(&mut self, bidder: AccountId, bid: Balance) -> Result<(), ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
+        require(self.auction.get().is_none(), ContractError::AlreadyExists("auction"))?;
+
+        let reserve_price = self.exit_price();
+        require(bid >= reserve_price.0, ContractError::InsufficientDeposit)?;
+
+        let auction = Auction {
+            reserve_price,
+            highest_bidder: bidder.clone(),
+            highest_bid: bid.into(),
+            end_time: (env::block_timestamp() + AUCTION_DURATION).into(),
+        };
+        self.auction.set(&auction);
 
         // Emit event
-        self.on_redeem(user_account, nft_contract_address, nft_token_id.clone());
+        self.on_auction_start(bidder, bid, auction.end_time);
+        Ok(())
+    }
+
+    fn internal_make_offer(&mut self, buyer: AccountId, amount: Balance) -> Result<(), ContractError> {
+        let SharesMetadata { released, .. } = self.ft_metadata();
+        require(!released, ContractError::InvalidState("token already redeemed"))?;
+        require(amount > 0, ContractError::InsufficientDeposit)?;
 
-        // Cleanup
-        self.cleanup();
+        let offer = self.offers.get(&buyer).unwrap_or(0) + amount;
+        self.offers.insert(&buyer, &offer);
+        self.offers_total += amount;
+
+        // Emit event
+        self.on_offer(buyer, offer);
+        Ok(())
     }
 
-    /// Once NFT is redeemed by paying NEAR tokens, remaining shareholders can claim their share of NEAR in vault
-    pub fn claim(&mut self) {
-        let SharesMetadata { released,  nft_contract_address, nft_token_id, .. } = self.ft_metadata();
-        assert!(released, "token not redeemed");
+    /// Release the NFT to a buyer, the price goes to the vault for holders to claim pro-rata
+    fn internal_sell(&mut self, buyer: AccountId, price: Balance) {
+        // Set as redeemed
+        let mut new_metadata = self.ft_metadata();
+        new_metadata.set_as_released();
+        self.metadata.replace(&new_metadata);
 
-        let user_account = env::signer_account_id();
-        let user_account_object: ValidAccountId = user_account.clone().try_into().unwrap();
+        self.vault = price;
 
-        let user_shares = self.ft_balance_of(user_account_object.clone());
-        assert!(user_shares.0 > 0, "nothing to claim");
+        // Transfer NFT to buyer, the price is refunded if the transfer fails
+        self.release_nft(buyer, price, 0);
+    }
+
+    /// Transfer the NFT out, `on_nft_released` confirms the release or undoes it
+    fn release_nft(&mut self, receiver_id: AccountId, refund: Balance, shares: Balance) -> Promise {
+        let SharesMetadata { nft_token_id, nft_contract_address, .. } = self.ft_metadata();
+        self.releasing = true;
+
+        ext_nft_core::ext(nft_contract_address)
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(receiver_id.clone(), nft_token_id, None, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_NFT_RELEASED)
+                    .on_nft_released(receiver_id, refund.into(), shares.into())
+            )
+    }
+
+    /// Shares an account held when the proposal was created
+    fn voting_power(&self, proposal: &Proposal, account_id: &AccountId) -> Balance {
+        match self.snapshots.get(account_id) {
+            Some((proposal_id, balance)) if proposal_id == proposal.id => balance,
+            _ => self.token.accounts.get(account_id).unwrap_or(0),
+        }
+    }
+
+    /// Must run before any change to the share balance of an account
+    fn before_balance_change(&mut self, account_id: &AccountId) {
+        self.snapshot_balance(account_id);
+
+        let balance = self.token.accounts.get(account_id).unwrap_or(0);
+        self.near_dividends.settle(account_id, balance);
+        self.lts_dividends.settle(account_id, balance);
+    }
 
-        let claim_amount = self.vault_balance_of(user_account_object.clone());
-        assert!(claim_amount.0 > 0, "balance has already been claimed");
+    /// Split revenue between the current shares
+    fn internal_deposit_revenue(&mut self, asset: DividendAsset, depositor: AccountId, amount: Balance) -> Result<(), ContractError> {
+        require(amount > 0, ContractError::InvalidArgument("revenue must be positive"))?;
 
-        // Burn tokens- TODO check correctness
-        self.token.accounts.insert(&user_account, &0);
-        self.token.total_supply -= user_shares.0;
-        self.on_tokens_burned(user_account.clone(), user_shares.0);
+        let total_supply = self.token.total_supply;
+        match asset {
+            DividendAsset::Near => self.near_dividends.distribute(amount, total_supply)?,
+            DividendAsset::Lts => self.lts_dividends.distribute(amount, total_supply)?,
+        }
 
         // Emit event
-        self.on_claim(user_account.clone(), nft_contract_address, nft_token_id, user_shares);
+        self.on_revenue(depositor, asset, amount);
+        Ok(())
+    }
 
-        // Transfer NEAR to user
-        Promise::new(user_account.clone()).transfer(
-            claim_amount.0
-        ).then(shares::cleanup(
-            &env::current_account_id(),
-            0,
-            env::prepaid_gas() / 2
-        )); // TODO allow payment in NEP-141 fungible tokens
+    /// Pay all unclaimed dividends of a settled account
+    fn internal_pay_dividends(&mut self, account_id: &AccountId) {
+        let near_amount = self.near_dividends.take(account_id);
+        if near_amount > 0 {
+            self.pay_dividends(account_id.clone(), DividendAsset::Near, near_amount);
+        }
 
-        // self.cleanup();
+        let lts_amount = self.lts_dividends.take(account_id);
+        if lts_amount > 0 {
+            self.pay_dividends(account_id.clone(), DividendAsset::Lts, lts_amount);
+        }
     }
 
+    /// Transfer dividends, crediting them back if the transfer fails
+    fn pay_dividends(&mut self, receiver_id: AccountId, asset: DividendAsset, amount: Balance) -> Promise {
+        let transfer = match asset {
+            DividendAsset::Near => Promise::new(receiver_id.clone()).transfer(amount),
+            // LTS dividends only exist if LTS revenue was accepted from the token
+            DividendAsset::Lts => ext_ft_core::ext(self.lts_token.clone().expect("LTS token is set"))
+                .with_attached_deposit(1)
+                .with_static_gas(GAS_FOR_FT_TRANSFER)
+                .ft_transfer(receiver_id.clone(), amount.into(), None),
+        };
 
-    fn cleanup(&mut self) {
         // Emit event
+        self.on_dividends_claimed(receiver_id.clone(), asset, amount);
 
-        let shares_left = self.ft_total_supply();
-        if shares_left.0 == 0 {
-            // TODO Remove current contract address Fractose contract
+        transfer.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_ON_DIVIDENDS_PAID)
+                .on_dividends_paid(receiver_id, asset, amount.into())
+        )
+    }
 
-            // Delete contract if all shares have been burnt
-            Promise::new(env::current_account_id()).delete_account(
-                // "system".into()
-                env::signer_account_id() // Transfer any leftover NEAR tokens to redeemer
-            );
+    /// Record the balance of an account before it changes while a proposal is open
+    fn snapshot_balance(&mut self, account_id: &AccountId) {
+        let proposal = match self.proposal.get() {
+            Some(proposal) if proposal.is_open(env::block_timestamp()) => proposal,
+            _ => return,
+        };
+
+        match self.snapshots.get(account_id) {
+            Some((proposal_id, _)) if proposal_id == proposal.id => {}
+            _ => {
+                let balance = self.token.accounts.get(account_id).unwrap_or(0);
+                self.snapshots.insert(account_id, &(proposal.id, balance));
+            }
         }
     }
 
+    fn internal_bid(&mut self, bidder: AccountId, bid: Balance) -> Result<(), ContractError> {
+        let mut auction = self.auction.get().ok_or(ContractError::NotFound("auction"))?;
+        require(auction.is_open(env::block_timestamp()), ContractError::InvalidState("auction has ended"))?;
+        require(bid > auction.highest_bid.0, ContractError::InsufficientDeposit)?;
+
+        // Refund previous highest bidder
+        self.pay_out(auction.highest_bidder.clone(), auction.highest_bid.0);
+
+        auction.highest_bidder = bidder.clone();
+        auction.highest_bid = bid.into();
+        self.auction.set(&auction);
+
+        // Emit event
+        self.on_bid(bidder, bid);
+        Ok(())
+    }
+
+    /// Burn every share held by an account and unregister it from the token,
+    /// paying out its dividends and refunding the storage deposit the way `storage_unregister` does
+    fn internal_burn_all(&mut self, account_id: &AccountId) -> Balance {
+        self.before_balance_change(account_id);
+        self.internal_pay_dividends(account_id);
+
+        let balance = self.token.accounts.get(account_id).unwrap_or(0);
+        if balance > 0 {
+            self.token.internal_withdraw(account_id, balance);
+            self.on_tokens_burned(account_id.clone(), balance);
+        }
+
+        if self.token.accounts.remove(account_id).is_some() {
+            let storage_deposit = Balance::from(self.token.account_storage_usage) * env::storage_byte_cost();
+            Promise::new(account_id.clone()).transfer(storage_deposit);
+        }
+
+        balance
+    }
+
+    fn report_redeemed(&self) {
+        ext_factory::ext(self.factory.clone())
+            .with_static_gas(GAS_FOR_REPORT)
+            .report_redeemed();
+    }
+
     fn on_account_closed(&mut self, account_id: AccountId, balance: Balance) {
         log!("Closed @{} with {}", account_id, balance);
     }
@@ -254,12 +928,85 @@ impl Shares {
         log!("Redeem({}, {}, {}, {})", redeemer_address, nft_contract_address, nft_token_id, env::current_account_id());
     }
 
+    fn on_auction_start(&mut self, bidder_address: AccountId, bid: Balance, end_time: U64) {
+        log!("AuctionStart({}, {}, {}, {})", bidder_address, bid, end_time.0, env::current_account_id());
+    }
+
+    fn on_bid(&mut self, bidder_address: AccountId, bid: Balance) {
+        log!("Bid({}, {}, {})", bidder_address, bid, env::current_account_id());
+    }
+
+    fn on_offer(&mut self, buyer_address: AccountId, amount: Balance) {
+        log!("Offer({}, {}, {})", buyer_address, amount, env::current_account_id());
+    }
+
+    fn on_proposal(&mut self, proposer_address: AccountId, proposal_id: u64) {
+        log!("Proposal({}, {}, {})", proposer_address, proposal_id, env::current_account_id());
+    }
+
+    fn on_vote(&mut self, voter_address: AccountId, proposal_id: u64, approve: bool, power: Balance) {
+        log!("Vote({}, {}, {}, {}, {})", voter_address, proposal_id, approve, power, env::current_account_id());
+    }
+
+    fn on_proposal_executed(&mut self, proposal_id: u64) {
+        log!("ProposalExecuted({}, {})", proposal_id, env::current_account_id());
+    }
+
+    fn on_revenue(&mut self, depositor_address: AccountId, asset: DividendAsset, amount: Balance) {
+        log!("Revenue({}, {:?}, {}, {})", depositor_address, asset, amount, env::current_account_id());
+    }
+
+    fn on_dividends_claimed(&mut self, holder_address: AccountId, asset: DividendAsset, amount: Balance) {
+        log!("DividendsClaimed({}, {:?}, {}, {})", holder_address, asset, amount, env::current_account_id());
+    }
+
     fn on_claim(&mut self, claimant_address: AccountId, nft_contract_address: AccountId, nft_token_id: TokenId, shares_count: U128) {
         log!("Securitize({}, {}, {}, {}, {})", claimant_address, nft_contract_address, nft_token_id, env::current_account_id(), shares_count.0);
     }
 }
 
-near_contract_standards::impl_fungible_token_core!(Shares, token, on_tokens_burned);
+// Transfers snapshot and settle both balances first, so votes keep the weight held at
+// proposal creation and dividends accrued on the old balance are not lost
+#[near_bindgen]
+impl FungibleTokenCore for Shares {
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.before_balance_change(&env::predecessor_account_id());
+        self.before_balance_change(&receiver_id);
+        self.token.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> PromiseOrValue<U128> {
+        self.before_balance_change(&env::predecessor_account_id());
+        self.before_balance_change(&receiver_id);
+        self.token.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.token.ft_balance_of(account_id)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenResolver for Shares {
+    #[private]
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128 {
+        self.before_balance_change(&sender_id);
+        self.before_balance_change(&receiver_id);
+
+        let (used_amount, burned_amount) = self.token.internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+        if burned_amount > 0 {
+            self.on_tokens_burned(sender_id, burned_amount);
+        }
+        used_amount.into()
+    }
+}
+
 near_contract_standards::impl_fungible_token_storage!(Shares, token, on_account_closed);
 
 #[near_bindgen]
@@ -267,4 +1014,346 @@ impl SharesMetadataProvider for Shares {
     fn ft_metadata(&self) -> SharesMetadata {
         self.metadata.get().unwrap()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fractose_common::ShareTokenMetadata;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    fn context(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("nft.shares.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    /// Context of a call attaching NEAR at a given block time
+    fn paying_context(predecessor: &str, deposit: Balance, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("nft.shares.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(timestamp)
+            .build());
+    }
+
+    /// Methods called and NEAR transferred by the receipts created for an account
+    fn receipts_to(receiver: &str) -> (Vec<String>, Balance) {
+        let mut calls = vec![];
+        let mut transferred = 0;
+        for receipt in get_created_receipts() {
+            if receipt.receiver_id.as_str() != receiver {
+                continue;
+            }
+            for action in receipt.actions {
+                match action {
+                    VmAction::FunctionCall { function_name, .. } => calls.push(function_name),
+                    VmAction::Transfer { deposit } => transferred += deposit,
+                    _ => {}
+                }
+            }
+        }
+        (calls, transferred)
+    }
+
+    /// Context of a callback receiving the result of the promise it was chained to
+    fn callback_context(result: PromiseResult) {
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id("nft.shares.testnet".parse().unwrap())
+                .predecessor_account_id("nft.shares.testnet".parse().unwrap())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result]
+        );
+    }
+
+    fn shares_of_owner() -> Shares {
+        shares_with(None, &[])
+    }
+
+    /// 100 shares at a price of 1, the owner keeps what is not allocated to `holders`
+    fn shares_with(payment_token: Option<&str>, holders: &[(&str, u128)]) -> Shares {
+        context("fractose.testnet");
+        let token_metadata = ShareTokenMetadata {
+            name: "Solar farm".to_string(),
+            symbol: "SOLAR".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+        };
+        Shares::create(CreateSharesArgs {
+            nft_contract_address: "nft.testnet".parse().unwrap(),
+            nft_token_id: "1".to_string(),
+            owner_id: "owner.testnet".parse().unwrap(),
+            shares_count: 100.into(),
+            decimals: 0,
+            share_price: 1.into(),
+            share_holders: holders.iter().map(|(account, _)| account.parse().unwrap()).collect(),
+            n_shares: holders.iter().map(|(_, n)| (*n).into()).collect(),
+            payment_token: payment_token.map(|token| token.parse().unwrap()),
+            lts_token: None,
+            token_metadata
+        }).unwrap()
+    }
+
+    #[test]
+    fn approved_proposal_blocks_new_proposals_until_executed() {
+        let mut contract = shares_of_owner();
+        context("owner.testnet");
+        contract.create_proposal(ProposalKind::SetSharePrice { share_price: 2.into() }).unwrap();
+        contract.vote(true).unwrap();
+        assert!(contract.get_proposal().unwrap().is_approved());
+
+        assert_eq!(
+            contract.create_proposal(ProposalKind::SetSharePrice { share_price: 3.into() }).unwrap_err(),
+            ContractError::InvalidState("approved proposal is not executed")
+        );
+
+        contract.execute_proposal().unwrap();
+        assert_eq!(contract.ft_metadata().share_price.0, 2);
+        assert_eq!(contract.create_proposal(ProposalKind::SetSharePrice { share_price: 3.into() }), Ok(2));
+    }
+
+    #[test]
+    fn vault_share_does_not_overflow_with_yocto_amounts() {
+        let mut contract = shares_of_owner();
+        let supply: Balance = 1_000_000_000_000_000_000_000_000;
+        contract.token.internal_deposit(&"owner.testnet".parse().unwrap(), supply - 100);
+        contract.token.internal_register_account(&"alice.testnet".parse().unwrap());
+        contract.token.internal_transfer(&"owner.testnet".parse().unwrap(), &"alice.testnet".parse().unwrap(), supply / 4, None);
+
+        let mut metadata = contract.ft_metadata();
+        metadata.set_as_released();
+        contract.metadata.replace(&metadata);
+        contract.vault = 2_000_000_000_000_000_000_000_000_000;
+
+        assert_eq!(contract.vault_balance_of("alice.testnet".parse().unwrap()).0, 500_000_000_000_000_000_000_000_000);
+        assert_eq!(contract.vault_balance_of("owner.testnet".parse().unwrap()).0, 1_500_000_000_000_000_000_000_000_000);
+    }
+
+    #[test]
+    fn failed_payout_is_credited_back_until_withdrawn() {
+        let mut contract = shares_of_owner();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        contract.pay_out(alice.clone(), 10);
+        assert_eq!(contract.payouts_outstanding, 10);
+
+        callback_context(PromiseResult::Failed);
+        contract.on_paid(alice.clone(), 10.into());
+        assert_eq!(contract.get_unpaid(alice.clone()), 10.into());
+        assert_eq!(contract.payouts_outstanding, 10);
+
+        context("alice.testnet");
+        assert!(contract.withdraw_unpaid().is_ok());
+        assert_eq!(contract.get_unpaid(alice.clone()), 0.into());
+        assert_eq!(contract.withdraw_unpaid().err(), Some(ContractError::NotFound("unpaid payout")));
+        assert_eq!(contract.payouts_outstanding, 10);
+
+        callback_context(PromiseResult::Successful(vec![]));
+        contract.on_paid(alice, 10.into());
+        assert_eq!(contract.payouts_outstanding, 0);
+    }
+
+    #[test]
+    fn migrate_reads_tagged_and_untagged_layouts() {
+        let mut contract = shares_of_owner();
+        contract.lts_token = Some("light-token.testnet".parse().unwrap());
+        let current = contract.try_to_vec().unwrap();
+
+        // First layout with an upgrade path, before governance and dividends
+        let v1 = (&contract.token, &contract.metadata, &contract.auction, 7u128, &contract.factory).try_to_vec().unwrap();
+        env::storage_write(b"STATE", &v1);
+        let migrated = Shares::migrate().unwrap();
+        assert_eq!(migrated.version, StateVersion::V7);
+        assert_eq!(migrated.vault, 7);
+        assert_eq!(migrated.ft_balance_of("owner.testnet".parse().unwrap()), 100.into());
+        assert_eq!(migrated.lts_token, None);
+
+        // Last untagged layout, without the tag and the payout ledger
+        env::storage_write(b"STATE", &current[1..current.len() - 21]);
+        let migrated = Shares::migrate().unwrap();
+        assert_eq!(migrated.lts_token, contract.lts_token);
+        assert_eq!(migrated.try_to_vec().unwrap(), current);
+
+        env::storage_write(b"STATE", &current);
+        assert_eq!(Shares::migrate().unwrap().try_to_vec().unwrap(), current);
+
+        env::storage_write(b"STATE", &[1, 2, 3]);
+        assert_eq!(Shares::migrate().err(), Some(ContractError::InvalidState("unknown state layout")));
+        env::storage_remove(b"STATE");
+        assert_eq!(Shares::migrate().err(), Some(ContractError::NotFound("state")));
+    }
+
+    #[test]
+    fn auction_runs_from_the_reserve_price_to_the_winner() {
+        let mut contract = shares_of_owner();
+        paying_context("bob.testnet", 99, 0);
+        assert_eq!(contract.start_auction(), Err(ContractError::InsufficientDeposit));
+        paying_context("bob.testnet", 100, 0);
+        contract.start_auction().unwrap();
+        let auction = contract.get_auction().unwrap();
+        assert_eq!((auction.reserve_price.0, auction.highest_bid.0), (100, 100));
+        assert_eq!(auction.end_time.0, AUCTION_DURATION);
+        assert_eq!(contract.start_auction(), Err(ContractError::AlreadyExists("auction")));
+
+        // Outbidding refunds the previous bid through a confirmed payout
+        paying_context("carol.testnet", 100, 1);
+        assert_eq!(contract.bid(), Err(ContractError::InsufficientDeposit));
+        paying_context("carol.testnet", 150, 1);
+        contract.bid().unwrap();
+        assert_eq!(receipts_to("bob.testnet").1, 100);
+        assert_eq!(contract.payouts_outstanding, 100);
+        assert_eq!(contract.get_auction().unwrap().highest_bidder.as_str(), "carol.testnet");
+
+        paying_context("carol.testnet", 0, AUCTION_DURATION - 1);
+        assert_eq!(contract.end_auction(), Err(ContractError::TooEarly));
+        paying_context("dave.testnet", 200, AUCTION_DURATION);
+        assert_eq!(contract.bid(), Err(ContractError::InvalidState("auction has ended")));
+        contract.end_auction().unwrap();
+        assert!(contract.get_auction().is_none());
+        assert_eq!(contract.vault_balance(), 150.into());
+
+        // Holders claim once the NFT has left
+        context("owner.testnet");
+        assert_eq!(contract.claim(), Err(ContractError::ExecutionInProgress));
+        contract.on_nft_released(Ok(()), "carol.testnet".parse().unwrap(), 150.into(), 0.into());
+        contract.claim().unwrap();
+        assert_eq!(contract.vault, 0);
+        assert_eq!(contract.ft_total_supply(), 0.into());
+    }
+
+    #[test]
+    fn reserve_price_follows_the_share_price() {
+        let mut contract = shares_with(None, &[("alice.testnet", 40)]);
+        assert_eq!(contract.exit_price(), 100.into());
+        assert_eq!(contract.redeem_amount_of("alice.testnet".parse().unwrap()), Ok(60.into()));
+
+        context("owner.testnet");
+        contract.create_proposal(ProposalKind::SetSharePrice { share_price: 3.into() }).unwrap();
+        contract.vote(true).unwrap();
+        contract.execute_proposal().unwrap();
+        assert_eq!(contract.exit_price(), 300.into());
+        assert_eq!(contract.redeem_amount_of("alice.testnet".parse().unwrap()), Ok(180.into()));
+
+        paying_context("bob.testnet", 299, 0);
+        assert_eq!(contract.start_auction(), Err(ContractError::InsufficientDeposit));
+        paying_context("bob.testnet", 300, 0);
+        contract.start_auction().unwrap();
+        assert_eq!(contract.get_auction().unwrap().reserve_price, 300.into());
+    }
+
+    #[test]
+    fn claims_split_the_vault_pro_rata_and_the_last_takes_the_remainder() {
+        let mut contract = shares_with(None, &[("alice.testnet", 20), ("bob.testnet", 10)]);
+        context("alice.testnet");
+        assert_eq!(contract.claim(), Err(ContractError::InvalidState("token not redeemed")));
+        assert_eq!(contract.vault_balance_of("alice.testnet".parse().unwrap()), 0.into());
+
+        let mut metadata = contract.ft_metadata();
+        metadata.set_as_released();
+        contract.metadata.replace(&metadata);
+        contract.vault = 101;
+        assert_eq!(contract.vault_balance_of("alice.testnet".parse().unwrap()), 20.into());
+        assert_eq!(contract.vault_balance_of("bob.testnet".parse().unwrap()), 10.into());
+        assert_eq!(contract.vault_balance_of("owner.testnet".parse().unwrap()), 70.into());
+
+        contract.claim().unwrap();
+        assert_eq!(contract.payouts_outstanding, 20);
+        assert_eq!((contract.vault, contract.ft_total_supply().0), (81, 80));
+        assert_eq!(contract.claim(), Err(ContractError::InsufficientBalance));
+
+        context("bob.testnet");
+        contract.claim().unwrap();
+        assert_eq!((contract.vault, contract.ft_total_supply().0), (71, 70));
+
+        context("owner.testnet");
+        assert_eq!(contract.vault_balance_of("owner.testnet".parse().unwrap()), 71.into());
+        contract.claim().unwrap();
+        assert_eq!((contract.vault, contract.ft_total_supply().0), (0, 0));
+        assert_eq!(contract.payouts_outstanding, 101);
+    }
+
+    #[test]
+    fn payments_go_through_the_payment_token() {
+        let mut contract = shares_with(Some("usdc.testnet"), &[("alice.testnet", 40)]);
+        paying_context("bob.testnet", 100, 0);
+        assert_eq!(contract.start_auction(), Err(ContractError::InvalidArgument("pay by transferring the payment token")));
+
+        context("other-token.testnet");
+        assert_eq!(
+            contract.internal_on_transfer("bob.testnet".parse().unwrap(), 100, "start_auction".to_string()),
+            Err(ContractError::InvalidArgument("unsupported payment token"))
+        );
+        assert_eq!(
+            contract.internal_on_transfer("bob.testnet".parse().unwrap(), 100, "revenue".to_string()),
+            Err(ContractError::InvalidArgument("revenue is paid in NEAR or LTS"))
+        );
+
+        context("usdc.testnet");
+        assert_eq!(
+            contract.internal_on_transfer("bob.testnet".parse().unwrap(), 100, "buy".to_string()),
+            Err(ContractError::InvalidArgument("unknown payment action"))
+        );
+        assert_eq!(contract.internal_on_transfer("bob.testnet".parse().unwrap(), 100, "offer".to_string()), Ok(0));
+        assert_eq!(contract.get_offer("bob.testnet".parse().unwrap()), 100.into());
+
+        // Redeeming returns the change, the token contract refunds it to the sender
+        assert_eq!(contract.internal_on_transfer("alice.testnet".parse().unwrap(), 70, "redeem".to_string()), Ok(10));
+        assert_eq!(contract.vault, 60);
+        assert!(contract.ft_metadata().released);
+
+        // Payouts are token transfers
+        context("bob.testnet");
+        assert!(contract.withdraw_offer().is_ok());
+        assert_eq!(receipts_to("usdc.testnet").0, vec!["ft_transfer"]);
+        assert_eq!(receipts_to("bob.testnet").1, 0);
+    }
+
+    #[test]
+    fn dividends_accrue_to_holders_at_deposit_time() {
+        let mut contract = shares_of_owner();
+        paying_context("tenant.testnet", 0, 0);
+        assert_eq!(contract.deposit_revenue(), Err(ContractError::InvalidArgument("revenue must be positive")));
+        paying_context("tenant.testnet", 50, 0);
+        contract.deposit_revenue().unwrap();
+        assert_eq!(contract.unclaimed_dividends_of("owner.testnet".parse().unwrap()).near, 50.into());
+
+        // Dividends deposited before a transfer stay with the sender
+        contract.token.internal_register_account(&"alice.testnet".parse().unwrap());
+        paying_context("owner.testnet", 1, 0);
+        contract.ft_transfer("alice.testnet".parse().unwrap(), 25.into(), None);
+        paying_context("tenant.testnet", 40, 0);
+        contract.deposit_revenue().unwrap();
+        assert_eq!(contract.unclaimed_dividends_of("owner.testnet".parse().unwrap()).near, 80.into());
+        assert_eq!(contract.unclaimed_dividends_of("alice.testnet".parse().unwrap()).near, 10.into());
+
+        // Fractions of a yocto are rounded down
+        paying_context("tenant.testnet", 1, 0);
+        contract.deposit_revenue().unwrap();
+        assert_eq!(contract.unclaimed_dividends_of("alice.testnet".parse().unwrap()).near, 10.into());
+
+        context("alice.testnet");
+        contract.claim_dividends();
+        assert_eq!(receipts_to("alice.testnet").1, 10);
+        assert_eq!(contract.unclaimed_dividends_of("alice.testnet".parse().unwrap()).near, 0.into());
+        assert_eq!(contract.near_dividends.outstanding(), 60);
+
+        // A failed payout is credited back, a confirmed one is no longer outstanding
+        callback_context(PromiseResult::Failed);
+        contract.on_dividends_paid("alice.testnet".parse().unwrap(), DividendAsset::Near, 10.into());
+        assert_eq!(contract.unclaimed_dividends_of("alice.testnet".parse().unwrap()).near, 10.into());
+        assert_eq!(contract.near_dividends.outstanding(), 60);
+        context("alice.testnet");
+        contract.claim_dividends();
+        callback_context(PromiseResult::Successful(vec![]));
+        contract.on_dividends_paid("alice.testnet".parse().unwrap(), DividendAsset::Near, 10.into());
+        assert_eq!(contract.near_dividends.outstanding(), 50);
+    }
+}
@@ -1,10 +1,12 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
+use fractose_common::TokenId;
 use near_sdk::AccountId;
+use lightency_errors::{require, ContractError};
 
 pub const SHARES_FT_METADATA_SPEC: &str = "shares-ft-1.0.0";
-pub type TokenId = String;
+pub const MAX_SYMBOL_LENGTH: usize = 16;
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -20,7 +22,9 @@ pub struct SharesMetadata {
     pub nft_contract_address: AccountId,
     pub nft_token_id: TokenId,
     pub share_price: U128,
-    pub released: bool
+    pub released: bool,
+    /// NEP-141 token holding the exit price and vault, NEAR if `None`
+    pub payment_token: Option<AccountId>
 }
 
 pub trait SharesMetadataProvider {
@@ -28,15 +32,35 @@ pub trait SharesMetadataProvider {
 }
 
 impl SharesMetadata {
-    pub fn assert_valid(&self) {
-        assert_eq!(&self.spec, SHARES_FT_METADATA_SPEC);
-        assert_eq!(self.reference.is_some(), self.reference_hash.is_some());
+    pub fn assert_valid(&self) -> Result<(), ContractError> {
+        require(self.spec == SHARES_FT_METADATA_SPEC, ContractError::InvalidArgument("unknown metadata spec"))?;
+        require(!self.name.trim().is_empty(), ContractError::InvalidArgument("name cannot be empty"))?;
+        require(
+            !self.symbol.is_empty() && self.symbol.len() <= MAX_SYMBOL_LENGTH,
+            ContractError::InvalidArgument("symbol is empty or too long")
+        )?;
+        require(
+            self.symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-'),
+            ContractError::InvalidArgument("symbol can only contain uppercase letters, digits and dashes")
+        )?;
+        if let Some(icon) = &self.icon {
+            require(icon.starts_with("data:"), ContractError::InvalidArgument("icon has to be a data URL"))?;
+        }
+        require(
+            self.reference.is_some() == self.reference_hash.is_some(),
+            ContractError::InvalidArgument("reference and reference hash go together")
+        )?;
         if let Some(reference_hash) = &self.reference_hash {
-            assert_eq!(reference_hash.0.len(), 32, "Hash has to be 32 bytes");
+            require(reference_hash.0.len() == 32, ContractError::InvalidArgument("hash has to be 32 bytes"))?;
         }
+        Ok(())
     }
 
     pub fn set_as_released(&mut self) {
         self.released = true;
     }
+
+    pub fn set_as_unreleased(&mut self) {
+        self.released = false;
+    }
 }
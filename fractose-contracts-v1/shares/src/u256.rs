@@ -0,0 +1,6 @@
+//! 256-bit integer for products of balances that overflow u128
+#![allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+
+uint::construct_uint! {
+    pub struct U256(4);
+}
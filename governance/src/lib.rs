@@ -0,0 +1,637 @@
+//! Proposal and vote logic shared by the Lightency DAO contracts
+//!
+//! A `Proposal` is generic over the action it carries, each contract defines its own
+//! action kind. Times are block timestamps in nanoseconds, as returned by
+//! `env::block_timestamp()`, and are passed in so the logic does not depend on the runtime.
+use lightency_errors::{require, ContractError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const NANOS_PER_MINUTE: u64 = 60 * 1_000_000_000;
+pub const NANOS_PER_HOUR: u64 = 60 * NANOS_PER_MINUTE;
+pub const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+
+/// Voting period of a proposal
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Duration {
+    pub days: u64,
+    pub hours: u64,
+    pub minutes: u64,
+}
+
+impl Duration {
+    pub fn new(days: u64, hours: u64, minutes: u64) -> Self {
+        Self {
+            days,
+            hours,
+            minutes,
+        }
+    }
+
+    pub fn as_nanos(&self) -> u64 {
+        self.days * NANOS_PER_DAY + self.hours * NANOS_PER_HOUR + self.minutes * NANOS_PER_MINUTE
+    }
+}
+
+/// Choice of a voter on a proposal
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VoteChoice {
+    Yes,
+    No,
+    /// Counted as a vote without weighing on the outcome
+    Abstain,
+    /// Counts against, and blocks the proposal once vetoes reach a third of the decisive weight
+    Veto,
+}
+
+// Vote structure
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Vote {
+    pub address: String,
+    pub choice: VoteChoice,
+    pub time_of_vote: u64,
+}
+
+/// Weighted result of a proposal, delegated weight included
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Tally {
+    pub yes: u32,
+    pub no: u32,
+    pub abstain: u32,
+    pub veto: u32,
+}
+
+impl Tally {
+    fn add(&mut self, choice: VoteChoice, weight: u32) {
+        match choice {
+            VoteChoice::Yes => self.yes += weight,
+            VoteChoice::No => self.no += weight,
+            VoteChoice::Abstain => self.abstain += weight,
+            VoteChoice::Veto => self.veto += weight,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.yes + self.no + self.abstain + self.veto
+    }
+
+    // More yes than no and veto together, and vetoes below a third of the yes, no and veto weight
+    pub fn is_approved(&self) -> bool {
+        self.yes > self.no + self.veto && self.veto * 3 < self.yes + self.no + self.veto
+    }
+
+    // Yes above `threshold` percent of the yes, no and veto weight
+    pub fn reaches(&self, threshold: u8) -> bool {
+        self.yes as u64 * 100 > threshold as u64 * (self.yes + self.no + self.veto) as u64
+    }
+}
+
+/// Delegation graph of a DAO, each member delegates to at most one representative
+///
+/// Delegations can be chained, the weight of a member who did not vote goes to the
+/// first account up the chain who did. A member voting directly always overrides
+/// their delegation.
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Delegations {
+    /// Delegator to representative
+    delegates: BTreeMap<String, String>,
+}
+
+impl Delegations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Delegate the weight of `delegator` to `representative`, replacing a previous delegation
+    pub fn delegate(
+        &mut self,
+        delegator: String,
+        representative: String,
+    ) -> Result<(), ContractError> {
+        require(
+            delegator != representative,
+            ContractError::InvalidArgument("can't delegate to yourself"),
+        )?;
+        let mut next = Some(&representative);
+        while let Some(account) = next {
+            require(
+                *account != delegator,
+                ContractError::InvalidArgument("delegation would create a cycle"),
+            )?;
+            next = self.delegates.get(account);
+        }
+        self.delegates.insert(delegator, representative);
+        Ok(())
+    }
+
+    // Remove the delegation of `delegator`, returns the former representative
+    pub fn undelegate(&mut self, delegator: &str) -> Result<String, ContractError> {
+        self.delegates
+            .remove(delegator)
+            .ok_or(ContractError::NotFound("delegation"))
+    }
+
+    // Remove every delegation from or to `account`, used when a member leaves
+    pub fn remove_member(&mut self, account: &str) {
+        self.delegates
+            .retain(|delegator, representative| delegator != account && representative != account);
+    }
+
+    pub fn delegate_of(&self, delegator: &str) -> Option<String> {
+        self.delegates.get(delegator).cloned()
+    }
+
+    // Members delegating directly to `representative`
+    pub fn delegators_of(&self, representative: &str) -> Vec<String> {
+        self.delegates
+            .iter()
+            .filter(|(_, r)| r.as_str() == representative)
+            .map(|(d, _)| d.clone())
+            .collect()
+    }
+
+    // Every delegation as (delegator, representative)
+    pub fn list(&self) -> Vec<(String, String)> {
+        self.delegates
+            .iter()
+            .map(|(d, r)| (d.clone(), r.clone()))
+            .collect()
+    }
+}
+
+/// Execution state of an accepted proposal
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ExecutionStatus {
+    NotExecuted,
+    /// Waiting for the callback of a cross-contract action
+    InProgress,
+    Executed,
+    /// The cross-contract action failed, the proposal can be executed again
+    Failed,
+}
+
+// Proposal structure, `K` is the action executed once the proposal is accepted
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal<K> {
+    pub proposal_name: String,
+    pub description: String,
+    pub proposal_creator: String,
+    pub kind: K,
+    pub time_of_creation: u64,
+    pub duration: Duration,
+    pub votes: Vec<Vote>,
+    pub execution: ExecutionStatus,
+}
+
+impl<K> Proposal<K> {
+    pub fn new(
+        proposal_name: String,
+        description: String,
+        proposal_creator: String,
+        kind: K,
+        time_of_creation: u64,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            proposal_name,
+            description,
+            proposal_creator,
+            kind,
+            time_of_creation,
+            duration,
+            votes: Vec::new(),
+            execution: ExecutionStatus::NotExecuted,
+        }
+    }
+
+    // Record a vote, a voter can change their vote while the proposal is running
+    // Votes close at the end of the voting period, or once the proposal has been executed
+    pub fn create_vote(
+        &mut self,
+        voter: String,
+        choice: VoteChoice,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        require(
+            !self.is_expired(now) && self.execution == ExecutionStatus::NotExecuted,
+            ContractError::VotingClosed,
+        )?;
+
+        match self.votes.iter_mut().find(|v| v.address == voter) {
+            Some(vote) => {
+                vote.choice = choice;
+                vote.time_of_vote = now;
+            }
+            None => self.votes.push(Vote {
+                address: voter,
+                choice,
+                time_of_vote: now,
+            }),
+        }
+        Ok(())
+    }
+
+    pub fn vote_of(&self, voter: &str) -> Option<VoteChoice> {
+        self.votes
+            .iter()
+            .find(|v| v.address == voter)
+            .map(|v| v.choice)
+    }
+
+    // Count direct votes, plus the weight of delegators who did not vote
+    pub fn tally(&self, delegations: &Delegations) -> Tally {
+        self.tally_among(delegations, |_| true)
+    }
+
+    // Same as `tally`, counting only the votes and delegations of eligible accounts.
+    // Delegated weight skips representatives who are not eligible
+    pub fn tally_among(&self, delegations: &Delegations, eligible: impl Fn(&str) -> bool) -> Tally {
+        let choices: BTreeMap<&str, VoteChoice> = self
+            .votes
+            .iter()
+            .filter(|v| eligible(&v.address))
+            .map(|v| (v.address.as_str(), v.choice))
+            .collect();
+
+        let mut tally = Tally::default();
+        for choice in choices.values() {
+            tally.add(*choice, 1);
+        }
+        for delegator in delegations.delegates.keys() {
+            if choices.contains_key(delegator.as_str()) || !eligible(delegator) {
+                continue;
+            }
+            let mut next = delegations.delegates.get(delegator);
+            while let Some(representative) = next {
+                if let Some(choice) = choices.get(representative.as_str()) {
+                    tally.add(*choice, 1);
+                    break;
+                }
+                next = delegations.delegates.get(representative);
+            }
+        }
+        tally
+    }
+
+    // Get the end time of a proposal
+    pub fn end_time(&self) -> u64 {
+        self.time_of_creation + self.duration.as_nanos()
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.end_time()
+    }
+
+    // Check if the voting period is over and the proposal was approved
+    pub fn check_proposal(&self, now: u64, delegations: &Delegations) -> bool {
+        self.check_proposal_among(now, delegations, |_| true)
+    }
+
+    // Same as `check_proposal`, on the tally of eligible accounts
+    pub fn check_proposal_among(
+        &self,
+        now: u64,
+        delegations: &Delegations,
+        eligible: impl Fn(&str) -> bool,
+    ) -> bool {
+        self.is_expired(now) && self.tally_among(delegations, eligible).is_approved()
+    }
+
+    // Mark the proposal as being executed, rejects a proposal executed or executing
+    pub fn start_execution(&mut self) -> Result<(), ContractError> {
+        match self.execution {
+            ExecutionStatus::NotExecuted | ExecutionStatus::Failed => {
+                self.execution = ExecutionStatus::InProgress;
+                Ok(())
+            }
+            ExecutionStatus::InProgress => Err(ContractError::ExecutionInProgress),
+            ExecutionStatus::Executed => Err(ContractError::AlreadyExecuted),
+        }
+    }
+
+    // Record the outcome of the execution
+    pub fn finish_execution(&mut self, success: bool) -> Result<(), ContractError> {
+        require(
+            self.execution == ExecutionStatus::InProgress,
+            ContractError::InvalidState("proposal is not being executed"),
+        )?;
+        self.execution = if success {
+            ExecutionStatus::Executed
+        } else {
+            ExecutionStatus::Failed
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal(duration: Duration) -> Proposal<u8> {
+        Proposal::new(
+            "proposal".to_string(),
+            "description".to_string(),
+            "alice.testnet".to_string(),
+            0,
+            1_000,
+            duration,
+        )
+    }
+
+    #[test]
+    fn duration_in_nanoseconds() {
+        assert_eq!(Duration::new(1, 0, 0).as_nanos(), 86_400_000_000_000);
+        assert_eq!(Duration::new(0, 1, 0).as_nanos(), 3_600_000_000_000);
+        assert_eq!(Duration::new(0, 0, 1).as_nanos(), 60_000_000_000);
+        assert_eq!(Duration::new(1, 2, 3).as_nanos(), 93_780_000_000_000);
+    }
+
+    #[test]
+    fn end_time_adds_duration_to_creation() {
+        let proposal = proposal(Duration::new(0, 0, 1));
+        assert_eq!(proposal.end_time(), 1_000 + NANOS_PER_MINUTE);
+        assert!(!proposal.is_expired(proposal.end_time()));
+        assert!(proposal.is_expired(proposal.end_time() + 1));
+    }
+
+    #[test]
+    fn create_vote_records_voter_and_tally() {
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000)
+            .unwrap();
+        proposal
+            .create_vote("carol.testnet".to_string(), VoteChoice::No, 3_000)
+            .unwrap();
+        proposal
+            .create_vote("dave.testnet".to_string(), VoteChoice::Abstain, 4_000)
+            .unwrap();
+
+        assert_eq!(
+            proposal.tally(&Delegations::new()),
+            Tally {
+                yes: 1,
+                no: 1,
+                abstain: 1,
+                veto: 0
+            }
+        );
+        assert_eq!(
+            proposal.votes[1],
+            Vote {
+                address: "carol.testnet".to_string(),
+                choice: VoteChoice::No,
+                time_of_vote: 3_000,
+            }
+        );
+    }
+
+    #[test]
+    fn create_vote_changes_previous_vote() {
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000)
+            .unwrap();
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::No, 3_000)
+            .unwrap();
+
+        assert_eq!(proposal.votes.len(), 1);
+        assert_eq!(proposal.vote_of("bob.testnet"), Some(VoteChoice::No));
+        assert_eq!(proposal.votes[0].time_of_vote, 3_000);
+    }
+
+    #[test]
+    fn create_vote_rejects_expired_proposal() {
+        let mut proposal = proposal(Duration::new(0, 0, 1));
+        let after_end = proposal.end_time() + 1;
+        assert_eq!(
+            proposal.create_vote("bob.testnet".to_string(), VoteChoice::Yes, after_end),
+            Err(ContractError::VotingClosed)
+        );
+    }
+
+    #[test]
+    fn check_proposal_requires_end_and_majority() {
+        let delegations = Delegations::new();
+        let mut proposal = proposal(Duration::new(0, 1, 0));
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000)
+            .unwrap();
+        let after_end = proposal.end_time() + 1;
+
+        assert!(!proposal.check_proposal(2_000, &delegations));
+        assert!(proposal.check_proposal(after_end, &delegations));
+
+        proposal
+            .create_vote("carol.testnet".to_string(), VoteChoice::No, 3_000)
+            .unwrap();
+        assert!(!proposal.check_proposal(after_end, &delegations));
+    }
+
+    #[test]
+    fn failed_execution_can_be_retried() {
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal.start_execution().unwrap();
+        proposal.finish_execution(false).unwrap();
+        assert_eq!(proposal.execution, ExecutionStatus::Failed);
+
+        proposal.start_execution().unwrap();
+        proposal.finish_execution(true).unwrap();
+        assert_eq!(proposal.execution, ExecutionStatus::Executed);
+    }
+
+    #[test]
+    fn start_execution_rejects_running_or_executed_proposal() {
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal.start_execution().unwrap();
+        assert_eq!(
+            proposal.start_execution(),
+            Err(ContractError::ExecutionInProgress)
+        );
+        proposal.finish_execution(true).unwrap();
+        assert_eq!(
+            proposal.start_execution(),
+            Err(ContractError::AlreadyExecuted)
+        );
+        assert_eq!(
+            proposal.finish_execution(false),
+            Err(ContractError::InvalidState(
+                "proposal is not being executed"
+            ))
+        );
+    }
+
+    #[test]
+    fn veto_blocks_a_majority() {
+        let tally = Tally {
+            yes: 5,
+            no: 1,
+            abstain: 0,
+            veto: 2,
+        };
+        assert!(tally.is_approved());
+        assert!(!Tally { veto: 3, ..tally }.is_approved());
+        // Vetoes below the blocking third still count against
+        assert!(!Tally {
+            yes: 4,
+            no: 2,
+            ..tally
+        }
+        .is_approved());
+    }
+
+    #[test]
+    fn reaches_threshold_of_decisive_weight() {
+        let tally = Tally {
+            yes: 2,
+            no: 1,
+            abstain: 5,
+            veto: 0,
+        };
+        assert!(tally.reaches(50));
+        assert!(tally.reaches(66));
+        assert!(!tally.reaches(67));
+    }
+
+    #[test]
+    fn delegated_weight_follows_the_chain_to_a_voter() {
+        let mut delegations = Delegations::new();
+        delegations
+            .delegate("carol.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("dave.testnet".to_string(), "carol.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("erin.testnet".to_string(), "frank.testnet".to_string())
+            .unwrap();
+
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000)
+            .unwrap();
+        assert_eq!(proposal.tally(&delegations).yes, 3);
+
+        // A delegator voting directly takes their weight back
+        proposal
+            .create_vote("carol.testnet".to_string(), VoteChoice::No, 3_000)
+            .unwrap();
+        let tally = proposal.tally(&delegations);
+        assert_eq!((tally.yes, tally.no), (1, 2));
+        assert_eq!(tally.total(), 3);
+    }
+
+    #[test]
+    fn create_vote_rejects_finalized_proposal() {
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal.start_execution().unwrap();
+        proposal.finish_execution(false).unwrap();
+        assert_eq!(
+            proposal.create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000),
+            Err(ContractError::VotingClosed)
+        );
+    }
+
+    #[test]
+    fn tally_among_ignores_ineligible_accounts() {
+        let mut delegations = Delegations::new();
+        delegations
+            .delegate("carol.testnet".to_string(), "dave.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("dave.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("erin.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+
+        let mut proposal = proposal(Duration::new(1, 0, 0));
+        proposal
+            .create_vote("bob.testnet".to_string(), VoteChoice::Yes, 2_000)
+            .unwrap();
+        proposal
+            .create_vote("dave.testnet".to_string(), VoteChoice::No, 2_000)
+            .unwrap();
+
+        // Dave's vote is ignored, Carol's weight goes on to Bob, Erin's is dropped
+        let eligible = |account: &str| account != "dave.testnet" && account != "erin.testnet";
+        let tally = proposal.tally_among(&delegations, eligible);
+        assert_eq!((tally.yes, tally.no), (2, 0));
+    }
+
+    #[test]
+    fn delegation_views() {
+        let mut delegations = Delegations::new();
+        delegations
+            .delegate("carol.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("dave.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+
+        assert_eq!(
+            delegations.delegators_of("bob.testnet"),
+            vec!["carol.testnet", "dave.testnet"]
+        );
+        assert_eq!(
+            delegations.undelegate("dave.testnet"),
+            Ok("bob.testnet".to_string())
+        );
+        assert_eq!(delegations.delegate_of("dave.testnet"), None);
+        assert_eq!(
+            delegations.undelegate("dave.testnet"),
+            Err(ContractError::NotFound("delegation"))
+        );
+
+        delegations.remove_member("bob.testnet");
+        assert!(delegations.list().is_empty());
+    }
+
+    #[test]
+    fn delegate_rejects_self_and_cycles() {
+        let mut delegations = Delegations::new();
+        assert_eq!(
+            delegations.delegate("bob.testnet".to_string(), "bob.testnet".to_string()),
+            Err(ContractError::InvalidArgument("can't delegate to yourself"))
+        );
+        delegations
+            .delegate("carol.testnet".to_string(), "bob.testnet".to_string())
+            .unwrap();
+        delegations
+            .delegate("bob.testnet".to_string(), "dave.testnet".to_string())
+            .unwrap();
+        assert_eq!(
+            delegations.delegate("dave.testnet".to_string(), "carol.testnet".to_string()),
+            Err(ContractError::InvalidArgument(
+                "delegation would create a cycle"
+            ))
+        );
+    }
+}
@@ -0,0 +1,8 @@
+#!/bin/sh
+
+echo ">> Building relay"
+
+rustup target add wasm32-unknown-unknown
+RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
+mkdir -p ./res
+cp target/wasm32-unknown-unknown/release/relay.wasm ./res/relay.wasm
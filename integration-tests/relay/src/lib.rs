@@ -0,0 +1,27 @@
+//! Intermediary contract used by the sandbox tests
+//!
+//! Forwards any call it receives, so a user calling it is still the signer of the
+//! forwarded call. Contracts that authorize by `signer_account_id` would let it act for
+//! the user, contracts that authorize by `predecessor_account_id` see the relay instead.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, near_bindgen, AccountId, Gas, Promise};
+
+const GAS_FOR_FORWARD: Gas = Gas(50_000_000_000_000);
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct Relay {}
+
+#[near_bindgen]
+impl Relay {
+    /// Call `method_name` on `receiver_id` with JSON `args` and the attached deposit
+    #[payable]
+    pub fn forward(&mut self, receiver_id: AccountId, method_name: String, args: String) -> Promise {
+        Promise::new(receiver_id).function_call(
+            method_name,
+            args.into_bytes(),
+            env::attached_deposit(),
+            GAS_FOR_FORWARD,
+        )
+    }
+}
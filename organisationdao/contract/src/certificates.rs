@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use near_contract_standards::non_fungible_token::core::{NonFungibleTokenCore, NonFungibleTokenResolver};
+use near_contract_standards::non_fungible_token::events::NftMint;
+use near_contract_standards::non_fungible_token::metadata::{
+    NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
+};
+use near_contract_standards::non_fungible_token::{Token, TokenId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, log, near_bindgen, AccountId, FunctionError, PromiseOrValue};
+use lightency_errors::{require, ContractError};
+use serde::{Serialize, Deserialize};
+
+use crate::{RegistrationDao, RegistrationDaoExt};
+
+// Energy certified by one certificate, 1 MWh
+pub const KWH_PER_CERTIFICATE: u64 = 1000;
+
+// Consumption of a certificate, once retired it can't be transferred anymore
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+pub struct Retirement {
+    pub retired_by: String,
+    pub retired_at: u64,
+}
+
+// Energy attribute certificate, minted as a NEP-171 token to the owner of the device
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+pub struct Certificate {
+    pub id: TokenId,
+    pub dao_name: String,
+    pub device_id: u64,
+    // Timestamps of the first and last readings the energy comes from
+    pub period_start: u64,
+    pub period_end: u64,
+    pub kwh: u64,
+    pub issuer: String,
+    pub issued_at: u64,
+    pub retirement: Option<Retirement>,
+}
+
+impl RegistrationDao {
+    fn get_active_certificate(&self, certificate_id: &TokenId) -> Result<Certificate, ContractError> {
+        let certificate = self.get_certificate(certificate_id.clone())?;
+        require(certificate.retirement.is_none(), ContractError::InvalidState("certificate has been retired"))?;
+        Ok(certificate)
+    }
+
+    // The NEP-171 methods can't return a Result, they panic with the error instead
+    fn assert_transferable(&self, certificate_id: &TokenId) {
+        if let Err(error) = self.get_active_certificate(certificate_id) {
+            error.panic();
+        }
+    }
+}
+
+#[near_bindgen]
+impl RegistrationDao {
+    /*** CERTIFICATES ***/
+
+    // Issue one certificate per KWH_PER_CERTIFICATE of uncertified readings of a device,
+    // at most `limit` of them, only the issuer of the dao can.
+    // Readings submitted before a dissolution can still be certified
+    #[handle_result]
+    pub fn issue_certificates(&mut self, device_id: u64, limit: u64) -> Result<Vec<TokenId>, ContractError> {
+        let mut device = self.get_device(device_id)?;
+        let issuer = self.get_dao(device.dao_name.clone())?.assert_issuer()?;
+        let owner: AccountId = device.owner.parse().map_err(|_| ContractError::InvalidArgument("device owner"))?;
+        let readings = self.readings.get(&device_id).ok_or(ContractError::NotFound("readings"))?;
+
+        let mut issued = Vec::new();
+        while (issued.len() as u64) < limit {
+            // Consume readings until a certificate worth of energy is pending
+            while device.pending_kwh < KWH_PER_CERTIFICATE && device.certified_readings < readings.len() {
+                let reading = readings.get(device.certified_readings).ok_or(ContractError::NotFound("reading"))?;
+                if device.pending_kwh == 0 {
+                    device.pending_since = reading.timestamp;
+                }
+                device.pending_kwh += reading.kwh;
+                device.certified_readings += 1;
+            }
+            if device.pending_kwh < KWH_PER_CERTIFICATE {
+                break;
+            }
+
+            let period_end = readings.get(device.certified_readings - 1).ok_or(ContractError::NotFound("reading"))?.timestamp;
+            let id = self.next_certificate_id.to_string();
+            self.next_certificate_id += 1;
+            let certificate = Certificate {
+                id: id.clone(),
+                dao_name: device.dao_name.clone(),
+                device_id,
+                period_start: device.pending_since,
+                period_end,
+                kwh: KWH_PER_CERTIFICATE,
+                issuer: issuer.clone(),
+                issued_at: env::block_timestamp(),
+                retirement: None,
+            };
+            let metadata = TokenMetadata {
+                title: Some(format!("Energy certificate #{}", id)),
+                description: Some(format!(
+                    "{} kWh produced by device {} of {}",
+                    certificate.kwh, device_id, certificate.dao_name
+                )),
+                media: None,
+                media_hash: None,
+                copies: None,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: Some(serde_json::to_string(&certificate).unwrap()),
+                reference: None,
+                reference_hash: None,
+            };
+            // Storage is paid by the contract, like devices and readings
+            self.tokens.internal_mint_with_refund(id.clone(), owner.clone(), Some(metadata), None);
+            self.certificates.insert(&id, &certificate);
+
+            // The rest of the last reading goes to the next certificate
+            device.pending_kwh -= KWH_PER_CERTIFICATE;
+            device.pending_since = period_end;
+            device.certified_kwh += KWH_PER_CERTIFICATE;
+            issued.push(id);
+        }
+        self.devices.insert(&device_id, &device);
+
+        if !issued.is_empty() {
+            let token_ids: Vec<&str> = issued.iter().map(|id| id.as_str()).collect();
+            NftMint { owner_id: &owner, token_ids: &token_ids, memo: None }.emit();
+        }
+        Ok(issued)
+    }
+
+    // Retire a certificate so its energy can't be claimed again, only its owner can
+    #[handle_result]
+    pub fn retire(&mut self, certificate_id: TokenId) -> Result<(), ContractError> {
+        let mut certificate = self.get_active_certificate(&certificate_id)?;
+        let account = env::predecessor_account_id();
+        require(
+            self.tokens.owner_by_id.get(&certificate_id) == Some(account.clone()),
+            ContractError::Unauthorized("owner of the certificate"),
+        )?;
+        certificate.retirement = Some(Retirement {
+            retired_by: account.to_string(),
+            retired_at: env::block_timestamp(),
+        });
+        self.certificates.insert(&certificate_id, &certificate);
+        log!("Certificate {} retired by {}", certificate_id, account);
+        Ok(())
+    }
+
+    #[handle_result]
+    pub fn get_certificate(&self, certificate_id: TokenId) -> Result<Certificate, ContractError> {
+        self.certificates.get(&certificate_id).ok_or(ContractError::NotFound("certificate"))
+    }
+
+    // Energy of a device that can't be certified yet
+    #[handle_result]
+    pub fn get_uncertified_kwh(&self, device_id: u64) -> Result<u64, ContractError> {
+        let device = self.get_device(device_id)?;
+        Ok(device.total_kwh - device.certified_kwh)
+    }
+}
+
+// NEP-171, retired certificates can't be transferred
+#[near_bindgen]
+impl NonFungibleTokenCore for RegistrationDao {
+    #[payable]
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        self.assert_transferable(&token_id);
+        self.tokens.nft_transfer(receiver_id, token_id, approval_id, memo)
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        self.assert_transferable(&token_id);
+        self.tokens.nft_transfer_call(receiver_id, token_id, approval_id, memo, msg)
+    }
+
+    fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.tokens.nft_token(token_id)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenResolver for RegistrationDao {
+    #[private]
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool {
+        self.tokens.nft_resolve_transfer(previous_owner_id, receiver_id, token_id, approved_account_ids)
+    }
+}
+
+near_contract_standards::impl_non_fungible_token_enumeration!(RegistrationDao, tokens);
+
+#[near_bindgen]
+impl NonFungibleTokenMetadataProvider for RegistrationDao {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "Lightency Energy Certificates".to_string(),
+            symbol: "LEC".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+}
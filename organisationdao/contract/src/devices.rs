@@ -0,0 +1,160 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
+use near_sdk::{env, near_bindgen};
+use lightency_errors::{require, ContractError};
+use serde::{Serialize, Deserialize};
+
+use crate::{RegistrationDao, RegistrationDaoExt, StorageKey};
+
+// Description of a metering device
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceMetadata {
+    pub name: String,
+    pub location: String,
+    // Solar, wind, hydro...
+    pub energy_source: String,
+    pub capacity_kw: u64,
+}
+
+// Metering device of a dao, readings are accepted once a council member approved it
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+pub struct Device {
+    pub id: u64,
+    pub dao_name: String,
+    pub owner: String,
+    pub metadata: DeviceMetadata,
+    pub approved: bool,
+    pub registered_at: u64,
+    // Time of the latest reading, readings must come in order
+    pub last_reading_time: u64,
+    pub total_kwh: u64,
+    //Certification progress, see certificates
+    pub certified_kwh: u64,
+    // Readings already counted in a certificate
+    pub certified_readings: u64,
+    // Energy of counted readings left for the next certificate, and the time it starts at
+    pub pending_kwh: u64,
+    pub pending_since: u64,
+}
+
+// Energy produced by a device since its previous reading
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+pub struct Reading {
+    pub kwh: u64,
+    // Time of the measure, in nanoseconds
+    pub timestamp: u64,
+    pub submitted_at: u64,
+}
+
+#[near_bindgen]
+impl RegistrationDao {
+    /*** DEVICES ***/
+
+    // Register a device owned by the caller, a member of the dao
+    #[handle_result]
+    pub fn register_device(&mut self, dao_name: String, metadata: DeviceMetadata) -> Result<u64, ContractError> {
+        let owner = env::predecessor_account_id().to_string();
+        let dao = self.get_dao(dao_name.clone())?;
+        dao.assert_active()?;
+        require(dao.is_member(&owner), ContractError::Unauthorized("dao members"))?;
+
+        let id = self.next_device_id;
+        self.next_device_id += 1;
+        let device = Device {
+            id,
+            dao_name: dao_name.clone(),
+            owner,
+            metadata,
+            approved: false,
+            registered_at: env::block_timestamp(),
+            last_reading_time: 0,
+            total_kwh: 0,
+            certified_kwh: 0,
+            certified_readings: 0,
+            pending_kwh: 0,
+            pending_since: 0,
+        };
+        self.devices.insert(&id, &device);
+        self.readings.insert(&id, &Vector::new(StorageKey::DeviceReadings { device_id: id }));
+
+        let mut dao_devices = self.dao_devices.get(&dao_name).unwrap_or_default();
+        dao_devices.push(id);
+        self.dao_devices.insert(&dao_name, &dao_devices);
+        Ok(id)
+    }
+
+    // Approve a registered device, only the council of its dao can
+    #[handle_result]
+    pub fn approve_device(&mut self, device_id: u64) -> Result<(), ContractError> {
+        let mut device = self.get_device(device_id)?;
+        let account = env::predecessor_account_id().to_string();
+        let dao = self.get_dao(device.dao_name.clone())?;
+        dao.assert_active()?;
+        require(dao.is_council_member(&account), ContractError::Unauthorized("council of the dao"))?;
+        require(!device.approved, ContractError::InvalidState("device is already approved"))?;
+        device.approved = true;
+        self.devices.insert(&device_id, &device);
+        Ok(())
+    }
+
+    // Append a reading, only the owner of an approved device can
+    #[handle_result]
+    pub fn submit_reading(&mut self, device_id: u64, kwh: u64, timestamp: u64) -> Result<(), ContractError> {
+        let mut device = self.get_device(device_id)?;
+        require(
+            env::predecessor_account_id().as_str() == device.owner,
+            ContractError::Unauthorized("device owner"),
+        )?;
+        require(device.approved, ContractError::InvalidState("device is not approved yet"))?;
+        self.get_dao(device.dao_name.clone())?.assert_active()?;
+        require(timestamp > device.last_reading_time, ContractError::InvalidArgument("reading is older than the last one"))?;
+        require(timestamp <= env::block_timestamp(), ContractError::InvalidArgument("reading can't be in the future"))?;
+
+        let mut readings = self.readings.get(&device_id).ok_or(ContractError::NotFound("readings"))?;
+        readings.push(&Reading {
+            kwh,
+            timestamp,
+            submitted_at: env::block_timestamp(),
+        });
+        self.readings.insert(&device_id, &readings);
+
+        device.last_reading_time = timestamp;
+        device.total_kwh += kwh;
+        self.devices.insert(&device_id, &device);
+        Ok(())
+    }
+
+    #[handle_result]
+    pub fn get_device(&self, device_id: u64) -> Result<Device, ContractError> {
+        self.devices.get(&device_id).ok_or(ContractError::NotFound("device"))
+    }
+
+    // Get the devices of a dao, by page
+    #[handle_result]
+    pub fn get_dao_devices(&self, dao_name: String, from_index: u64, limit: u64) -> Result<Vec<Device>, ContractError> {
+        self.dao_devices
+            .get(&dao_name)
+            .unwrap_or_default()
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|id| self.devices.get(id).ok_or(ContractError::NotFound("device")))
+            .collect()
+    }
+
+    #[handle_result]
+    pub fn get_number_readings(&self, device_id: u64) -> Result<u64, ContractError> {
+        self.get_device(device_id)?;
+        Ok(self.readings.get(&device_id).ok_or(ContractError::NotFound("readings"))?.len())
+    }
+
+    // Get the readings of a device from the oldest, by page
+    #[handle_result]
+    pub fn get_readings(&self, device_id: u64, from_index: u64, limit: u64) -> Result<Vec<Reading>, ContractError> {
+        self.get_device(device_id)?;
+        let readings = self.readings.get(&device_id).ok_or(ContractError::NotFound("readings"))?;
+        (from_index..std::cmp::min(from_index.saturating_add(limit), readings.len()))
+            .map(|index| readings.get(index).ok_or(ContractError::NotFound("reading")))
+            .collect()
+    }
+}
@@ -1,246 +1,78 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{Vector, UnorderedMap};
+use near_sdk::collections::{LookupMap, Vector, UnorderedMap};
 use near_sdk::ext_contract;
-use near_sdk::{env, near_bindgen, Gas, AccountId};
+use near_sdk::{env, log, near_bindgen, BorshStorageKey, Gas, AccountId};
 use serde::{Serialize,Deserialize};
+use lightency_governance::{Delegations, Duration, Proposal, Tally, VoteChoice};
+use lightency_errors::{require, ContractError};
+
+use near_contract_standards::non_fungible_token::{NonFungibleToken, TokenId};
+
+mod certificates;
+mod devices;
+
+pub use certificates::{Certificate, Retirement, KWH_PER_CERTIFICATE};
+pub use devices::{Device, DeviceMetadata, Reading};
 
 //external contracts
 #[ext_contract(ext_ft)]
 pub trait PlatformDao {
-    fn create_proposal(&mut self,proposal_type:u16 ,proposal_name: String ,description: String ,dao_name:String ,dao_purpose:String ,duration_days:u64 ,duration_hours:u64 ,duration_min:u64);
+    fn request_proposal(&mut self,requester:String ,proposal_type:u16 ,proposal_name: String ,description: String ,dao_name:String ,dao_purpose:String ,duration_days:u64 ,duration_hours:u64 ,duration_min:u64);
 }
 
-#[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-pub struct Device {
-    address : String,
-    data : String,
-    time_of_generation : String,
-}
-
-// #[near_bindgen]
-// #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-// pub struct certificate {
-//     address : String,
-//     privacy : String,
-// }
-
-// VOTE
-// Vote structor 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
- pub struct Vote{
-    pub address: String,
-    pub vote:u8,
-    pub time_of_vote:u64,
- }
-
- // Vote implementation 
- impl Vote {
-    // Initialise a new vote
-    pub fn new() -> Self{
-        Self {
-            address: String::new(),
-            vote:0,
-            time_of_vote:0,
-        }
-    }
- }
-
 // Council Members Proposal
-// Proposal structor
+// Action of a member proposal, applied to the members once accepted
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-pub struct MemberProposal{
-    pub proposal_name: String,
-    pub description: String,
-    pub proposal_creator: String,
-    pub beneficiary:String,
-    pub votes_for: u32,
-    pub votes_against: u32,
-    pub time_of_creation:u64,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64,
-    pub list_voters:Vec<String>,
-    pub votes:Vec<Vote>,
+pub enum MemberAction{
+    // Add the beneficiary to the council
+    NominateCouncil{ beneficiary:String },
+    // Accept a pending application to the community
+    AcceptApplication{ applicant:String },
+    // Remove a council or community member
+    RemoveMember{ account:String },
 }
 
-impl MemberProposal{
-    pub fn new() -> Self{
-        Self{
-        proposal_name: String::new(),
-        description: String::new(),
-        proposal_creator: String::new(),
-        beneficiary:String::new(),
-        votes_for: 0,
-        votes_against: 0,
-        time_of_creation:0,
-        duration_days:0,
-        duration_hours:0,
-        duration_min:0,
-        list_voters:Vec::new(),
-        votes:Vec::new(),
-}
-    }
-    // Create a new vote 
-    // Returns a propsal contains the new vote 
-    pub fn create_vote(&mut self, vote:u8) -> Self{
-        for i in self.list_voters.clone(){
-            assert!(
-                env::signer_account_id().to_string() != i,
-                "You already voted"
-            );
-        }
-        let v = Vote{
-            address: env::signer_account_id().to_string(),
-            vote:vote,
-            time_of_vote:env::block_timestamp(),
-        };
-        self.votes.push(v);
-        if vote==0 {
-            self.votes_against=self.votes_against+1;
-        }else{
-            self.votes_for=self.votes_for+1;
-        }
-        self.list_voters.push(env::signer_account_id().to_string());
-        Self { 
-            proposal_name: self.proposal_name.clone(), 
-            description: self.description.clone(),
-            proposal_creator: self.proposal_creator.clone(),
-            beneficiary:self.beneficiary.clone(),
-            votes_for: self.votes_for, 
-            votes_against: self.votes_against, 
-            time_of_creation: self.time_of_creation, 
-            duration_days: self.duration_days, 
-            duration_hours: self.duration_hours, 
-            duration_min: self.duration_min, 
-            list_voters: self.list_voters.clone(),
-            votes: self.votes.clone() 
-        }
-    }
-
-    // Get the end time of a proposal 
-    pub fn end_time(&self) -> u64 {
-        self.time_of_creation+(self.duration_days*86400000000+self.duration_hours*3600000000+self.duration_min*60000000)
-    }
-
-    // Check if the time of a proposal is end or not 
-    pub fn check_proposal(&self)->bool{
-        if (env::block_timestamp() > self.end_time()) && (self.votes_for > self.votes_against){
-            return true;
-        }
-        return false;
-    } 
-}
+pub type MemberProposal = Proposal<MemberAction>;
 
 // PROPOSAL
-// Proposal structor 
+// Action of a dao proposal
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
-pub struct Proposals{
+pub struct DaoRequest{
     pub proposal_type: u8,
-    pub proposal_name: String,
-    pub description: String,
-    pub proposal_creator: String,
-    pub votes_for: u32,
-    pub votes_against: u32,
-    pub time_of_creation:u64,
-    pub duration_days:u64,
-    pub duration_hours:u64,
-    pub duration_min:u64,
-    pub list_voters:Vec<String>,
-    pub votes:Vec<Vote>,
 }
 
-// Proposal implementation
-impl Proposals {
-    // Initialise a new proposal
-    pub fn new() -> Self{
-        Self {
-            proposal_type:0,
-            proposal_name: String::new(),
-            description: String::new(),
-            proposal_creator: String::new(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:0,
-            duration_days:0,
-            duration_hours:0,
-            duration_min:0,
-            list_voters:Vec::new(),
-            votes:Vec::new(),
-        }
-    }
+// Dao proposal, votes and tally live in the shared governance crate
+pub type Proposals = Proposal<DaoRequest>;
 
-    // Create a new vote 
-    // Returns a propsal contains the new vote 
-    pub fn create_vote(&mut self, vote:u8) -> Self{
-        for i in self.list_voters.clone(){
-            assert!(
-                env::signer_account_id().to_string() != i,
-                "You already voted"
-            );
-        }
-        let v = Vote{
-            address: env::signer_account_id().to_string(),
-            vote:vote,
-            time_of_vote:env::block_timestamp(),
-        };
-        self.votes.push(v);
-        if vote==0 {
-            self.votes_against=self.votes_against+1;
-        }else{
-            self.votes_for=self.votes_for+1;
-        }
-        self.list_voters.push(env::signer_account_id().to_string());
-        Self { 
-            proposal_type:self.proposal_type,
-            proposal_name: self.proposal_name.clone(), 
-            description: self.description.clone(),
-            proposal_creator: self.proposal_creator.clone(),
-            votes_for: self.votes_for, 
-            votes_against: self.votes_against, 
-            time_of_creation: self.time_of_creation, 
-            duration_days: self.duration_days, 
-            duration_hours: self.duration_hours, 
-            duration_min: self.duration_min, 
-            list_voters: self.list_voters.clone(),
-            votes: self.votes.clone() 
-        }
-    }
 
-    // Get the end time of a proposal 
-    pub fn end_time(&self) -> u64 {
-        self.time_of_creation+(self.duration_days*86400000000+self.duration_hours*3600000000+self.duration_min*60000000)
-    }
-
-    // Check if the time of a proposal is end or not 
-    pub fn check_proposal(&self)->bool{
-        if (env::block_timestamp() > self.end_time()) && (self.votes_for > self.votes_against){
-            return true;
-        }
-        return false;
-    }
+// DAO
+// Lifecycle of a dao, a dissolved dao is frozen but its history stays readable
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DaoStatus {
+    Active,
+    Dissolved { dissolved_at: u64 },
 }
 
-
-// DAO
 // Dao structor
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, Debug, Serialize)]
 pub struct Dao {
     pub council_members : Vec<String>,
     pub community_members : Vec<String>,
-    // pub devices_list : UnorderedMap<AccountId, Vec<Device>>,
-    // certificates_list : UnorderedMap<AccountId, certificate>,
     pub dao_name: String,
     pub dao_purpose: String,
     pub founder: String,
-    pub numb_council_members: u64,
-    pub numb_community_members:u64,
+    pub status: DaoStatus,
+    //Accounts waiting to join the community
+    pub applications: Vec<String>,
     //proposal
     pub number_of_proposals:u16,
     pub proposals : Vec<Proposals>,
     pub member_proposals: Vec<MemberProposal>,
     //Voting
+    pub delegations: Delegations,
+    //Certified issuer assigned by the platform
+    pub issuer: Option<String>,
     pub duration_days:u64,
     pub duration_hours:u64,
     pub duration_min:u64
@@ -253,76 +85,134 @@ impl Dao {
         Self {
             council_members : Vec::new(),
             community_members: Vec::new(),
-            //devices_list : UnorderedMap::new(b"m"),
             dao_name:String::new(),
             dao_purpose:String::new(),
             founder:"".to_string().try_into().unwrap(),
-            numb_council_members:0,
-            numb_community_members:0,
+            status:DaoStatus::Active,
+            applications:Vec::new(),
             number_of_proposals:0,
             proposals:Vec::new(),
             member_proposals:Vec::new(),
+            delegations:Delegations::new(),
+            issuer:None,
             duration_days:0,
             duration_hours:0,
             duration_min:0,
         }
     }
-    pub fn create_member_proposal(&mut self,proposal_name: String,beneficiary:String,description: String){
-        let proposal=MemberProposal{
-            proposal_name: proposal_name,
-            description: description,
-            beneficiary: beneficiary,
-            proposal_creator: env::signer_account_id().to_string(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:env::block_timestamp(),
-            duration_days:self.duration_days,
-            duration_hours:self.duration_hours,
-            duration_min:self.duration_min,
-            list_voters:Vec::new(),
-            votes:Vec::new()
+    // Make sure that the caller of the function is the issuer assigned to the dao
+    pub fn assert_issuer(&self) -> Result<String, ContractError>{
+        let account = env::predecessor_account_id().to_string();
+        require(self.issuer.as_ref() == Some(&account), ContractError::Unauthorized("issuer of the dao"))?;
+        Ok(account)
+    }
 
-        };
+    // Make sure that the dao has not been dissolved
+    pub fn assert_active(&self) -> Result<(), ContractError>{
+        require(self.status == DaoStatus::Active, ContractError::DaoDissolved)
+    }
+
+    // Check if an account is a council or community member of the dao
+    pub fn is_member(&self, account: &str) -> bool{
+        self.is_council_member(account) || self.community_members.iter().any(|m| m == account)
+    }
+
+    pub fn is_council_member(&self, account: &str) -> bool{
+        self.council_members.iter().any(|m| m == account)
+    }
+
+    // Tally of a proposal, council and community members vote
+    pub fn tally(&self, proposal: &Proposals) -> Tally{
+        proposal.tally_among(&self.delegations, |account| self.is_member(account))
+    }
+
+    // Tally of a member proposal, only the council votes
+    pub fn member_tally(&self, proposal: &MemberProposal) -> Tally{
+        proposal.tally_among(&self.delegations, |account| self.is_council_member(account))
+    }
+
+    // Check if the voting period of a proposal is over and its eligible voters approved it
+    pub fn check_proposal(&self, proposal: &Proposals) -> bool{
+        proposal.check_proposal_among(env::block_timestamp(), &self.delegations, |account| self.is_member(account))
+    }
+
+    pub fn check_member_proposal(&self, proposal: &MemberProposal) -> bool{
+        proposal.check_proposal_among(env::block_timestamp(), &self.delegations, |account| self.is_council_member(account))
+    }
+
+    // Accept an application, the applicant joins the community
+    pub fn accept_application(&mut self, applicant: &String) -> Result<(), ContractError>{
+        let index = self.applications.iter().position(|a| a == applicant).ok_or(ContractError::NotFound("application"))?;
+        self.applications.remove(index);
+        self.community_members.push(applicant.clone());
+        Ok(())
+    }
+
+    // Remove a council or community member, the council always keeps one member
+    pub fn remove_member(&mut self, account: &String) -> Result<(), ContractError>{
+        if let Some(index) = self.council_members.iter().position(|m| m == account) {
+            require(self.council_members.len() > 1, ContractError::InvalidState("the council can't lose its last member"))?;
+            self.council_members.remove(index);
+        } else {
+            let index = self.community_members.iter().position(|m| m == account).ok_or(ContractError::NotFound("member"))?;
+            self.community_members.remove(index);
+        }
+        self.delegations.remove_member(account);
+        Ok(())
+    }
+
+    // Only members of an active dao can propose
+    pub fn create_member_proposal(&mut self,proposal_name: String,action:MemberAction,description: String) -> Result<(), ContractError>{
+        self.assert_active()?;
+        let proposer = env::predecessor_account_id().to_string();
+        require(self.is_member(&proposer), ContractError::Unauthorized("dao members"))?;
+        match &action {
+            MemberAction::NominateCouncil{ beneficiary } => require(!self.council_members.contains(beneficiary), ContractError::AlreadyExists("council member"))?,
+            MemberAction::AcceptApplication{ applicant } => require(self.applications.contains(applicant), ContractError::NotFound("application"))?,
+            MemberAction::RemoveMember{ account } => require(self.is_member(account), ContractError::NotFound("member"))?,
+        }
+        let proposal=MemberProposal::new(
+            proposal_name,
+            description,
+            proposer,
+            action,
+            env::block_timestamp(),
+            Duration::new(self.duration_days, self.duration_hours, self.duration_min),
+        );
         self.member_proposals.push(proposal);
+        Ok(())
     }
 
-    // Create a new proposal in a dao 
+    // Create a new proposal in a dao, only its members can
     pub fn create_proposal (
         &mut self,
         proposal_type:u8,
         proposal_name: String,
         description: String,
-    ){
-        let proposal=Proposals{
-            proposal_type:proposal_type,
-            proposal_name: proposal_name,
-            description: description,
-            proposal_creator: env::signer_account_id().to_string(),
-            votes_for: 0,
-            votes_against: 0,
-            time_of_creation:env::block_timestamp(),
-            duration_days:self.duration_days,
-            duration_hours:self.duration_hours,
-            duration_min:self.duration_min,
-            list_voters:Vec::new(),
-            votes:Vec::new()
-        };
+    ) -> Result<(), ContractError>{
+        self.assert_active()?;
+        let proposer = env::predecessor_account_id().to_string();
+        require(self.is_member(&proposer), ContractError::Unauthorized("dao members"))?;
+        let proposal=Proposals::new(
+            proposal_name,
+            description,
+            proposer,
+            DaoRequest{ proposal_type },
+            env::block_timestamp(),
+            Duration::new(self.duration_days, self.duration_hours, self.duration_min),
+        );
         self.proposals.push(proposal);
+        Ok(())
     }
 
     // Replace a proposal whith a new one 
-    pub fn replace_proposal(&mut self, proposal: Proposals){
-        let mut index =0;
-        for i in 0..self.proposals.len(){
-            match self.proposals.get(i){
-                Some(p) => if p.proposal_name==proposal.proposal_name {
-                    index=i;
-                },
-                None => panic!("There is no PROPOSALs"),
-            }
-        }
-        self.proposals.swap_remove(index);
-        self.proposals.insert(index, proposal);
+    pub fn replace_proposal(&mut self, proposal: Proposals) -> Result<(), ContractError>{
+        let index = self.proposals
+            .iter()
+            .position(|p| p.proposal_name == proposal.proposal_name)
+            .ok_or(ContractError::NotFound("proposal"))?;
+        self.proposals[index] = proposal;
+        Ok(())
     }
 
     // Get all proposals 
@@ -331,31 +221,21 @@ impl Dao {
     }
 
     // Get a spsific proposal 
-    pub fn get_specific_proposal(&self, proposal_name: String) -> Proposals{
-        let mut proposal= Proposals::new();
-        for i in 0..self.proposals.len() {
-            match self.proposals.get(i){
-                Some(p) => if p.proposal_name==proposal_name {
-                    proposal=p.clone();
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        proposal
+    pub fn get_specific_proposal(&self, proposal_name: String) -> Result<Proposals, ContractError>{
+        self.proposals
+            .iter()
+            .find(|p| p.proposal_name == proposal_name)
+            .cloned()
+            .ok_or(ContractError::NotFound("proposal"))
     }
     // Replace a member proposal whith a new one 
-    pub fn replace_member_proposal(&mut self, proposal: MemberProposal){
-        let mut index =0;
-        for i in 0..self.member_proposals.len(){
-            match self.member_proposals.get(i){
-                Some(p) => if p.proposal_name==proposal.proposal_name {
-                    index=i;
-                },
-                None => panic!("There is no member proposals"),
-            }
-        }
-        self.member_proposals.swap_remove(index);
-        self.member_proposals.insert(index, proposal);
+    pub fn replace_member_proposal(&mut self, proposal: MemberProposal) -> Result<(), ContractError>{
+        let index = self.member_proposals
+            .iter()
+            .position(|p| p.proposal_name == proposal.proposal_name)
+            .ok_or(ContractError::NotFound("member proposal"))?;
+        self.member_proposals[index] = proposal;
+        Ok(())
     }
 
     // Get all proposals 
@@ -364,17 +244,12 @@ impl Dao {
     }
 
     // Get a spsific proposal 
-    pub fn get_specific_member_proposal(&self, proposal_name: String) -> MemberProposal{
-        let mut proposal= MemberProposal::new();
-        for i in 0..self.member_proposals.len() {
-            match self.member_proposals.get(i){
-                Some(p) => if p.proposal_name==proposal_name {
-                    proposal=p.clone();
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        proposal
+    pub fn get_specific_member_proposal(&self, proposal_name: String) -> Result<MemberProposal, ContractError>{
+        self.member_proposals
+            .iter()
+            .find(|p| p.proposal_name == proposal_name)
+            .cloned()
+            .ok_or(ContractError::NotFound("member proposal"))
     }
 }
 
@@ -383,6 +258,30 @@ impl Dao {
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct RegistrationDao {
     daos: Vector<Dao>,
+    //Devices, kept outside the daos so readings don't grow them
+    next_device_id: u64,
+    devices: UnorderedMap<u64, Device>,
+    dao_devices: LookupMap<String, Vec<u64>>,
+    readings: LookupMap<u64, Vector<Reading>>,
+    //Energy certificates, NEP-171 tokens owned by the device owners
+    next_certificate_id: u64,
+    tokens: NonFungibleToken,
+    certificates: LookupMap<TokenId, Certificate>,
+    // Platform dao contract, creates and deletes daos once its proposals pass
+    platform_dao: AccountId,
+}
+
+// Storage prefixes of the device and certificate collections
+#[derive(BorshStorageKey, BorshSerialize)]
+enum StorageKey {
+    Devices,
+    DaoDevices,
+    Readings,
+    DeviceReadings { device_id: u64 },
+    TokenOwners,
+    TokenMetadata,
+    TokenEnumeration,
+    Certificates,
 }
 
 // Define the default, which automatically initializes the contract
@@ -392,184 +291,187 @@ impl Default for RegistrationDao {
     }
 }
 
-// Make sure that the caller of the function is the owner
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
-}
-
 // Make sure that the caller of the function is the platform account
-fn assert_platform() {
-    assert_eq!(
-        env::signer_account_id(),
-        "platformdao.testnet".to_string().try_into().unwrap(),
-        "Can only be called by platform account"
-    );
+fn assert_platform(contract: &RegistrationDao) -> Result<(), ContractError> {
+    require(
+        env::predecessor_account_id() == contract.platform_dao,
+        ContractError::Unauthorized("platform contract"),
+    )
 }
 
-// Make sure that the caller of the function is the issuer account
-fn assert_issuer() {
-    assert_eq!(
-        env::signer_account_id(),
-        "issuer.testnet".to_string().try_into().unwrap(),
-        "Can only be called by issuer account"
-    );
-}
 
+impl RegistrationDao {
+    // Find a dao and its index
+    fn find_dao(&self, dao_name: &String) -> Result<(u64, Dao), ContractError> {
+        self.daos
+            .iter()
+            .enumerate()
+            .find(|(_, dao)| dao.dao_name == *dao_name)
+            .map(|(index, dao)| (index as u64, dao))
+            .ok_or(ContractError::NotFound("dao"))
+    }
+}
 
 // Implement the contract structure
 // To be implemented in the front end
 #[near_bindgen]
 impl RegistrationDao {
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(platform_dao: AccountId) -> Self {
         Self {
             daos : Vector::new(b"a"),
+            next_device_id: 0,
+            devices: UnorderedMap::new(StorageKey::Devices),
+            dao_devices: LookupMap::new(StorageKey::DaoDevices),
+            readings: LookupMap::new(StorageKey::Readings),
+            next_certificate_id: 0,
+            tokens: NonFungibleToken::new(
+                StorageKey::TokenOwners,
+                env::current_account_id(),
+                Some(StorageKey::TokenMetadata),
+                Some(StorageKey::TokenEnumeration),
+                None::<StorageKey>,
+            ),
+            certificates: LookupMap::new(StorageKey::Certificates),
+            platform_dao,
         }
     }
 
-    // delete all daos
-    pub fn delete_all (&mut self){
-        assert_self();
-        for _i in 0..self.daos.len(){
-            self.daos.pop();
-        }
-    }
-
-    // delete a specific dao
-    pub fn delete (&mut self, dao_name: String){
-        assert_platform();
-        for i in 0..self.daos.len(){
-            match self.daos.get(i) {
-                Some(d) => if d.dao_name == dao_name {
-                    self.daos.swap_remove(i);
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
+    // Dissolve a dao once the platform approved it, its proposals, votes and members are kept
+    #[handle_result]
+    pub fn dissolve (&mut self, dao_name: String) -> Result<(), ContractError>{
+        assert_platform(self)?;
+        let (index, mut dao)=self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        let dissolved_at=env::block_timestamp();
+        dao.status=DaoStatus::Dissolved{ dissolved_at };
+        self.daos.replace(index, &dao);
+        log!(
+            "EVENT_JSON:{}",
+            serde_json::json!({
+                "standard": "lightency",
+                "version": "1.0.0",
+                "event": "dao_dissolved",
+                "data": [{ "dao_name": dao_name, "dissolved_at": dissolved_at }],
+            })
+        );
+        Ok(())
     }
 
     // METHODS
 
     /*** DAOS ***/
 
+    // Set the issuer assigned to a dao by the platform
+    #[handle_result]
+    pub fn set_issuer(&mut self, dao_name: String, issuer: Option<String>) -> Result<(), ContractError>{
+        assert_platform(self)?;
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.issuer=issuer;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Get the issuer assigned to a dao
+    #[handle_result]
+    pub fn get_issuer(&self, dao_name: String) -> Result<Option<String>, ContractError>{
+        Ok(self.get_dao(dao_name)?.issuer)
+    }
+
     // Create dao
+    #[handle_result]
     pub fn add_dao(
         &mut self,
         dao_name: String,
         dao_purpose: String,
+        founder: String,
         duration_days:u64,
         duration_hours:u64,
         duration_min:u64,
-    ) {
-        assert_platform();
+    ) -> Result<(), ContractError> {
+        assert_platform(self)?;
+        // Names stay taken by dissolved daos
+        require(!self.check_existance_dao(dao_name.clone()), ContractError::AlreadyExists("dao"))?;
         let mut dao = Dao {
             council_members:Vec::new(),
             community_members:Vec::new(),
-            //devices_list : UnorderedMap::new(b"m"),
             dao_name: dao_name,
             dao_purpose: dao_purpose,
-            founder:env::signer_account_id().to_string(),
-            numb_council_members: 1,
-            numb_community_members:0,
+            founder,
+            status: DaoStatus::Active,
+            applications: Vec::new(),
             number_of_proposals:0,
             proposals : Vec::new(),
             member_proposals: Vec::new(),
+            delegations: Delegations::new(),
+            issuer: None,
             duration_days:duration_days,
             duration_hours:duration_hours,
             duration_min:duration_min,
         };
         dao.council_members.push(dao.founder.clone());
         self.daos.push(&dao);
+        Ok(())
     }
 
     // get all daos
     pub fn get_all_daos(&self) -> Vec<Dao>{
-        let mut vec= Vec::new();
-        for i in 0..self.daos.len() {
-            match self.daos.get(i) {
-                Some(d) => vec.push(d),
-                None => panic!("There is no DAOs"),
-            }
-        }
-        vec
+        self.daos.iter().collect()
     }
 
     // get a specific dao
-    pub fn get_dao(&self, dao_name: String) -> Dao {
-        let mut dao= Dao::new();
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    dao=d;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        dao
+    #[handle_result]
+    pub fn get_dao(&self, dao_name: String) -> Result<Dao, ContractError> {
+        Ok(self.find_dao(&dao_name)?.1)
     }
 
     pub fn check_existance_dao (&self, dao_name: String) -> bool {
-        let mut res = false;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    res = true;
-                    break;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        res
+        self.daos.iter().any(|d| d.dao_name==dao_name)
     }
 
     /*** PROPOSALS ***/
     //create member proposal
 
-    pub fn create_member_proposal(&mut self,dao_name: String,beneficiary:String,proposal_name: String,description: String){
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len(){
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        dao.create_member_proposal(proposal_name, beneficiary, description);
+    #[handle_result]
+    pub fn create_member_proposal(&mut self,dao_name: String,beneficiary:String,proposal_name: String,description: String) -> Result<(), ContractError>{
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.create_member_proposal(proposal_name, MemberAction::NominateCouncil{ beneficiary }, description)?;
         self.daos.replace(index, &dao);
+        Ok(())
+    }
 
+    // create a proposal to accept an application to the community
+    #[handle_result]
+    pub fn create_application_proposal(&mut self,dao_name: String,applicant:String,proposal_name: String,description: String) -> Result<(), ContractError>{
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.create_member_proposal(proposal_name, MemberAction::AcceptApplication{ applicant }, description)?;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
 
+    // create a proposal to remove a member
+    #[handle_result]
+    pub fn create_removal_proposal(&mut self,dao_name: String,account:String,proposal_name: String,description: String) -> Result<(), ContractError>{
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.create_member_proposal(proposal_name, MemberAction::RemoveMember{ account }, description)?;
+        self.daos.replace(index, &dao);
+        Ok(())
     }
 
     // create proposal
     #[payable]
+    #[handle_result]
     pub fn create_proposal(
         &mut self,
         dao_name: String,
         proposal_type:u8,
         proposal_name: String,
         description: String,
-    ){
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        dao.create_proposal(proposal_type, proposal_name, description);
+    ) -> Result<(), ContractError>{
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.create_proposal(proposal_type, proposal_name, description)?;
         self.daos.replace(index, &dao);
+        Ok(())
     }
 
     // request of a dao creation
@@ -585,11 +487,13 @@ impl RegistrationDao {
         duration_min:u64
     ){
         // account as account id
-        let account = "platformdao.testnet".to_string().try_into().unwrap();
+        let account = self.platform_dao.clone();
 
+        // The platform only accepts the requester from this contract
         ext_ft::ext(account)
             .with_static_gas(Gas(5*1000000000000))
-            .create_proposal(proposal_type,
+            .request_proposal(env::predecessor_account_id().to_string(),
+                proposal_type,
                 proposal_name,
                 description,
                 dao_name,
@@ -601,218 +505,449 @@ impl RegistrationDao {
 
 
     // get all proposals in a specific dao 
-    pub fn get_all_proposals(&self,dao_name: String) -> Vec<Proposals> {
-        let dao=self.get_dao(dao_name);
-        dao.get_proposals()
+    #[handle_result]
+    pub fn get_all_proposals(&self,dao_name: String) -> Result<Vec<Proposals>, ContractError> {
+        let dao=self.get_dao(dao_name)?;
+        Ok(dao.get_proposals())
     }
 
     //get a specific proposal in a specific dao
-    pub fn get_proposal(&self, dao_name: String,proposal_name: String) -> Proposals{
-        let dao=self.get_dao(dao_name);
+    #[handle_result]
+    pub fn get_proposal(&self, dao_name: String,proposal_name: String) -> Result<Proposals, ContractError>{
+        let dao=self.get_dao(dao_name)?;
         dao.get_specific_proposal(proposal_name)
     }
 
     //get the end time of a specific proposal
-    pub fn get_end_time(&self, dao_name: String,proposal_name: String) -> u64 {
-        let proposal=self.get_proposal(dao_name, proposal_name);
-        proposal.end_time()
+    #[handle_result]
+    pub fn get_end_time(&self, dao_name: String,proposal_name: String) -> Result<u64, ContractError> {
+        let proposal=self.get_proposal(dao_name, proposal_name)?;
+        Ok(proposal.end_time())
     }
 
     //get all member proposals in a specific dao
-    pub fn get_all_member_proposals(&self,dao_name: String)-> Vec<MemberProposal>{
-        let dao=self.get_dao(dao_name);
-        dao.get_member_proposals()
+    #[handle_result]
+    pub fn get_all_member_proposals(&self,dao_name: String)-> Result<Vec<MemberProposal>, ContractError>{
+        let dao=self.get_dao(dao_name)?;
+        Ok(dao.get_member_proposals())
     }
      //get a specific member proposal in a specific dao
-     pub fn get_member_proposal(&self, dao_name: String,proposal_name: String) -> MemberProposal{
-        let dao=self.get_dao(dao_name);
+     #[handle_result]
+     pub fn get_member_proposal(&self, dao_name: String,proposal_name: String) -> Result<MemberProposal, ContractError>{
+        let dao=self.get_dao(dao_name)?;
         dao.get_specific_member_proposal(proposal_name)
     }
 
     //get the end time of a specific proposal
-    pub fn get_end_time_member(&self, dao_name: String,proposal_name: String) -> u64 {
-        let proposal=self.get_member_proposal(dao_name, proposal_name);
-        proposal.end_time()
+    #[handle_result]
+    pub fn get_end_time_member(&self, dao_name: String,proposal_name: String) -> Result<u64, ContractError> {
+        let proposal=self.get_member_proposal(dao_name, proposal_name)?;
+        Ok(proposal.end_time())
     }
 
     /*** Proposal VOTES ***/
 
     // add a vote 
+    #[handle_result]
     pub fn add_vote(
         &mut self,
         dao_name: String,
         proposal_name: String,
-        vote: u8
-    ){
-        let proposal =self.get_dao(dao_name.clone()).get_specific_proposal(proposal_name).create_vote(vote);
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(mut d) => if d.dao_name==dao_name {
-                    d.replace_proposal(proposal.clone());
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
+        vote: VoteChoice
+    ) -> Result<(), ContractError>{
+        let voter = env::predecessor_account_id().to_string();
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(dao.is_member(&voter), ContractError::Unauthorized("dao members"))?;
+        let mut proposal =dao.get_specific_proposal(proposal_name)?;
+        proposal.create_vote(voter, vote, env::block_timestamp())?;
+        dao.replace_proposal(proposal)?;
         self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // get the tally of a proposal, delegated votes included
+    #[handle_result]
+    pub fn get_tally(&self, dao_name: String,proposal_name: String) -> Result<Tally, ContractError> {
+        let dao= self.get_dao(dao_name)?;
+        Ok(dao.tally(&dao.get_specific_proposal(proposal_name)?))
     }
 
     // get votes for 
-    pub fn get_votes_for(&self, dao_name: String,proposal_name: String) -> u32 {
-        let proposal= self.get_dao(dao_name.clone()).get_specific_proposal(proposal_name);
-        proposal.votes_for
+    #[handle_result]
+    pub fn get_votes_for(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> {
+        Ok(self.get_tally(dao_name, proposal_name)?.yes)
     }
 
-    // get votes against 
-    pub fn get_votes_against(&self, dao_name: String,proposal_name: String) -> u32 {
-        let proposal= self.get_dao(dao_name.clone()).get_specific_proposal(proposal_name);
-        proposal.votes_against
+    // get votes against, vetoes included
+    #[handle_result]
+    pub fn get_votes_against(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> {
+        let tally= self.get_tally(dao_name, proposal_name)?;
+        Ok(tally.no + tally.veto)
     }
 
     // get number of votes 
-    pub fn get_number_votes(&self, dao_name: String,proposal_name: String) -> u32 { 
-        let proposal= self.get_dao(dao_name.clone()).get_specific_proposal(proposal_name);
-        proposal.votes_against + proposal.votes_for
+    #[handle_result]
+    pub fn get_number_votes(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> { 
+        Ok(self.get_tally(dao_name, proposal_name)?.total())
     }
 
     // check the proposal and return a message
-    pub fn check_the_proposal(&self, dao_name: String,proposal_name: String) -> String{
-        let proposal=self.get_proposal(dao_name, proposal_name);
-        let check= proposal.check_proposal();
+    #[handle_result]
+    pub fn check_the_proposal(&self, dao_name: String,proposal_name: String) -> Result<String, ContractError>{
+        let dao=self.get_dao(dao_name)?;
+        let proposal=dao.get_specific_proposal(proposal_name)?;
+        let check= dao.check_proposal(&proposal);
         if check==true {
             let msg="Proposal accepted".to_string();
-            msg
+            Ok(msg)
         }else{
             let msg="Proposal refused".to_string();
-            msg
+            Ok(msg)
         }
     }
 
     /*** Member Proposal VOTES ***/
 
     // add a vote 
+    #[handle_result]
     pub fn add_member_vote(
         &mut self,
         dao_name: String,
         proposal_name: String,
-        vote: u8
-    ){
-        let proposal =self.get_dao(dao_name.clone()).get_specific_member_proposal(proposal_name).create_vote(vote);
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(mut d) => if d.dao_name==dao_name {
-                    d.replace_member_proposal(proposal.clone());
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
+        vote: VoteChoice
+    ) -> Result<(), ContractError>{
+        let voter = env::predecessor_account_id().to_string();
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(dao.is_council_member(&voter), ContractError::Unauthorized("council of the dao"))?;
+        let mut proposal =dao.get_specific_member_proposal(proposal_name)?;
+        proposal.create_vote(voter, vote, env::block_timestamp())?;
+        dao.replace_member_proposal(proposal)?;
         self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // get the tally of a member proposal, delegated votes included
+    #[handle_result]
+    pub fn get_member_tally(&self, dao_name: String,proposal_name: String) -> Result<Tally, ContractError> {
+        let dao= self.get_dao(dao_name)?;
+        Ok(dao.member_tally(&dao.get_specific_member_proposal(proposal_name)?))
     }
 
     // get votes for 
-    pub fn get_member_votes_for(&self, dao_name: String,proposal_name: String) -> u32 {
-        let proposal= self.get_dao(dao_name.clone()).get_specific_member_proposal(proposal_name);
-        proposal.votes_for
+    #[handle_result]
+    pub fn get_member_votes_for(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> {
+        Ok(self.get_member_tally(dao_name, proposal_name)?.yes)
     }
 
-    // get votes against 
-    pub fn get_member_votes_against(&self, dao_name: String,proposal_name: String) -> u32 {
-        let proposal= self.get_dao(dao_name.clone()).get_specific_member_proposal(proposal_name);
-        proposal.votes_against
+    // get votes against, vetoes included
+    #[handle_result]
+    pub fn get_member_votes_against(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> {
+        let tally= self.get_member_tally(dao_name, proposal_name)?;
+        Ok(tally.no + tally.veto)
     }
 
     // get number of votes 
-    pub fn get_number_member_votes(&self, dao_name: String,proposal_name: String) -> u32 { 
-        let proposal= self.get_dao(dao_name.clone()).get_specific_member_proposal(proposal_name);
-        proposal.votes_against + proposal.votes_for
+    #[handle_result]
+    pub fn get_number_member_votes(&self, dao_name: String,proposal_name: String) -> Result<u32, ContractError> { 
+        Ok(self.get_member_tally(dao_name, proposal_name)?.total())
     }
 
     // check the proposal and return a message
-    pub fn check_the_member_proposal(&self, dao_name: String,proposal_name: String) -> String{
-        let proposal=self.get_member_proposal(dao_name, proposal_name);
-        let check= proposal.check_proposal();
+    #[handle_result]
+    pub fn check_the_member_proposal(&self, dao_name: String,proposal_name: String) -> Result<String, ContractError>{
+        let dao=self.get_dao(dao_name)?;
+        let proposal=dao.get_specific_member_proposal(proposal_name)?;
+        let check= dao.check_member_proposal(&proposal);
         if check==true {
             let msg="Proposal accepted".to_string();
-            msg
+            Ok(msg)
         }else{
             let msg="Proposal refused".to_string();
-            msg
+            Ok(msg)
         }
     }
 
 
-    // Add a council member to a dao 
-    pub fn process_member_proposal (&mut self, dao_name: String, proposal_name:String) {
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
-            }
-        }
-        let proposal=dao.get_specific_member_proposal(proposal_name);
-        if(proposal.check_proposal()){dao.council_members.push(proposal.beneficiary);
-        dao.numb_council_members = dao.numb_council_members + 1;
-        self.daos.replace(index, &dao);}
-    }
-
-    pub fn add_community_member (&mut self, dao_name: String, account:String) {
-        let mut dao= Dao::new();
-        let mut index=0;
-        for i in 0..self.daos.len() {
-            match self.daos.get(i){
-                Some(d) => if d.dao_name==dao_name {
-                    dao=d;
-                    index=i;
-                },
-                None => panic!("There is no DAOs"),
+    // Apply an accepted member proposal to the members of a dao
+    #[handle_result]
+    pub fn process_member_proposal (&mut self, dao_name: String, proposal_name:String) -> Result<(), ContractError> {
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        let mut proposal=dao.get_specific_member_proposal(proposal_name)?;
+        require(dao.check_member_proposal(&proposal), ContractError::NotApproved)?;
+        // Marked executed in the same call, a second call is rejected
+        proposal.start_execution()?;
+        proposal.finish_execution(true)?;
+        match &proposal.kind {
+            MemberAction::NominateCouncil{ beneficiary } => {
+                require(!dao.council_members.contains(beneficiary), ContractError::AlreadyExists("council member"))?;
+                // A community member moves to the council
+                dao.community_members.retain(|m| m != beneficiary);
+                dao.applications.retain(|a| a != beneficiary);
+                dao.council_members.push(beneficiary.clone());
             }
+            MemberAction::AcceptApplication{ applicant } => dao.accept_application(applicant)?,
+            MemberAction::RemoveMember{ account } => dao.remove_member(account)?,
         }
-        dao.community_members.push(account);
-        dao.numb_community_members = dao.numb_community_members + 1;
+        dao.replace_member_proposal(proposal)?;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    /*** MEMBERSHIP ***/
+
+    // Apply to join the community of a dao
+    #[handle_result]
+    pub fn apply(&mut self, dao_name: String) -> Result<(), ContractError> {
+        let account = env::predecessor_account_id().to_string();
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(!dao.is_member(&account), ContractError::AlreadyExists("member"))?;
+        require(!dao.applications.contains(&account), ContractError::AlreadyExists("application"))?;
+        dao.applications.push(account);
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Withdraw your pending application
+    #[handle_result]
+    pub fn withdraw_application(&mut self, dao_name: String) -> Result<(), ContractError> {
+        let account = env::predecessor_account_id().to_string();
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        let position = dao.applications.iter().position(|a| *a == account).ok_or(ContractError::NotFound("application"))?;
+        dao.applications.remove(position);
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Accept an application, only a council member can
+    #[handle_result]
+    pub fn approve_application(&mut self, dao_name: String, applicant: String) -> Result<(), ContractError> {
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(
+            dao.is_council_member(env::predecessor_account_id().as_str()),
+            ContractError::Unauthorized("council of the dao"),
+        )?;
+        dao.accept_application(&applicant)?;
         self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Reject an application, only a council member can
+    #[handle_result]
+    pub fn reject_application(&mut self, dao_name: String, applicant: String) -> Result<(), ContractError> {
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(
+            dao.is_council_member(env::predecessor_account_id().as_str()),
+            ContractError::Unauthorized("council of the dao"),
+        )?;
+        let position = dao.applications.iter().position(|a| *a == applicant).ok_or(ContractError::NotFound("application"))?;
+        dao.applications.remove(position);
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Leave a dao, the last council member can't
+    #[handle_result]
+    pub fn leave(&mut self, dao_name: String) -> Result<(), ContractError> {
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        dao.remove_member(&env::predecessor_account_id().to_string())?;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    #[handle_result]
+    pub fn get_applications(&self, dao_name: String) -> Result<Vec<String>, ContractError> {
+        Ok(self.get_dao(dao_name)?.applications)
+    }
+
+    #[handle_result]
+    pub fn get_number_council_members(&self, dao_name: String) -> Result<u64, ContractError> {
+        Ok(self.get_dao(dao_name)?.council_members.len() as u64)
+    }
+
+    #[handle_result]
+    pub fn get_number_community_members(&self, dao_name: String) -> Result<u64, ContractError> {
+        Ok(self.get_dao(dao_name)?.community_members.len() as u64)
     }
-    pub fn get_all_council_members(&self,dao_name: String)-> Vec<String> {
-        self.get_dao(dao_name).council_members
-    }
-    pub fn get_all_community_members(&self,dao_name: String)->Vec<String>{
-        self.get_dao(dao_name).community_members
-    }
-    
-
-
-
-    // Add a new device
-    // pub fn add_device (&mut self, dao_name: String ,device_address: String ,device_data: String, device_time_of_generation: String ) {
-    //     let device = Device {
-    //         address:device_address,
-    //         data:device_data,
-    //         time_of_generation:device_time_of_generation
-    //     };
-    //     let mut dao= Dao::new();
-    //     let mut index=0;
-    //     for i in 0..self.daos.len() {
-    //         match self.daos.get(i){
-    //             Some(d) => if d.dao_name==dao_name {
-    //                 dao=d;
-    //                 index=i;
-    //             },
-    //             None => panic!("There is no DAOs"),
-    //         }
-    //     }
-    //     if self.check_existance_dao(dao_name) == true {
-    //     }
-    // }
 
+    /*** DELEGATION ***/
+
+    // Delegate your vote in a dao to another member
+    #[handle_result]
+    pub fn delegate(&mut self, dao_name: String, to: String) -> Result<(), ContractError> {
+        let account = env::predecessor_account_id().to_string();
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        require(dao.is_member(&account), ContractError::Unauthorized("dao members"))?;
+        require(dao.is_member(&to), ContractError::InvalidArgument("can only delegate to a dao member"))?;
+        dao.delegations.delegate(account, to)?;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Take back your delegated vote in a dao
+    #[handle_result]
+    pub fn undelegate(&mut self, dao_name: String) -> Result<(), ContractError> {
+        let (index, mut dao)= self.find_dao(&dao_name)?;
+        dao.assert_active()?;
+        dao.delegations.undelegate(env::predecessor_account_id().as_ref())?;
+        self.daos.replace(index, &dao);
+        Ok(())
+    }
+
+    // Get all delegations of a dao as (delegator, representative)
+    #[handle_result]
+    pub fn get_delegations(&self, dao_name: String) -> Result<Vec<(String, String)>, ContractError> {
+        Ok(self.get_dao(dao_name)?.delegations.list())
+    }
+
+    // Get the representative of a member
+    #[handle_result]
+    pub fn get_delegate_of(&self, dao_name: String, account: String) -> Result<Option<String>, ContractError> {
+        Ok(self.get_dao(dao_name)?.delegations.delegate_of(&account))
+    }
+
+    // Get the members delegating to an account
+    #[handle_result]
+    pub fn get_delegators_of(&self, dao_name: String, account: String) -> Result<Vec<String>, ContractError> {
+        Ok(self.get_dao(dao_name)?.delegations.delegators_of(&account))
+    }
+
+    #[handle_result]
+    pub fn get_all_council_members(&self,dao_name: String)-> Result<Vec<String>, ContractError> {
+        Ok(self.get_dao(dao_name)?.council_members)
+    }
+    #[handle_result]
+    pub fn get_all_community_members(&self,dao_name: String)->Result<Vec<String>, ContractError>{
+        Ok(self.get_dao(dao_name)?.community_members)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    const PLATFORM_DAO: &str = "platformdao.testnet";
+
+    fn context(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("organisationdao.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    fn contract_with_dao() -> RegistrationDao {
+        context(PLATFORM_DAO);
+        let mut contract = RegistrationDao::new(PLATFORM_DAO.parse().unwrap());
+        contract.add_dao("solar".to_string(), "purpose".to_string(), "founder.testnet".to_string(), 0, 0, 1).unwrap();
+        contract
+    }
+
+    #[test]
+    fn unknown_dao_is_not_found() {
+        let mut contract = contract_with_dao();
+        assert_eq!(contract.get_dao("wind".to_string()).unwrap_err(), ContractError::NotFound("dao"));
+        context("founder.testnet");
+        assert_eq!(contract.apply("wind".to_string()).unwrap_err(), ContractError::NotFound("dao"));
+    }
+
+    #[test]
+    fn only_the_platform_adds_and_dissolves_daos() {
+        let mut contract = contract_with_dao();
+        context("founder.testnet");
+        assert_eq!(
+            contract.dissolve("solar".to_string()).unwrap_err(),
+            ContractError::Unauthorized("platform contract")
+        );
+        context(PLATFORM_DAO);
+        assert_eq!(
+            contract.add_dao("solar".to_string(), "purpose".to_string(), "founder.testnet".to_string(), 0, 0, 1).unwrap_err(),
+            ContractError::AlreadyExists("dao")
+        );
+    }
+
+    #[test]
+    fn dissolved_dao_is_frozen() {
+        let mut contract = contract_with_dao();
+        contract.dissolve("solar".to_string()).unwrap();
+        assert_eq!(contract.dissolve("solar".to_string()).unwrap_err(), ContractError::DaoDissolved);
+        context("member.testnet");
+        assert_eq!(contract.apply("solar".to_string()).unwrap_err(), ContractError::DaoDissolved);
+        context("founder.testnet");
+        assert_eq!(
+            contract.reject_application("solar".to_string(), "member.testnet".to_string()).unwrap_err(),
+            ContractError::DaoDissolved
+        );
+    }
+
+    #[test]
+    fn only_members_create_member_proposals() {
+        let mut contract = contract_with_dao();
+        context("outsider.testnet");
+        contract.apply("solar".to_string()).unwrap();
+        assert_eq!(
+            contract.create_application_proposal("solar".to_string(), "outsider.testnet".to_string(), "join".to_string(), "".to_string()).unwrap_err(),
+            ContractError::Unauthorized("dao members")
+        );
+        context("founder.testnet");
+        contract.create_application_proposal("solar".to_string(), "outsider.testnet".to_string(), "join".to_string(), "".to_string()).unwrap();
+    }
+
+    #[test]
+    fn only_members_of_active_daos_create_proposals() {
+        let mut contract = contract_with_dao();
+        context("outsider.testnet");
+        assert_eq!(
+            contract.create_proposal("solar".to_string(), 0, "grant".to_string(), "".to_string()).unwrap_err(),
+            ContractError::Unauthorized("dao members")
+        );
+        context("founder.testnet");
+        contract.create_proposal("solar".to_string(), 0, "grant".to_string(), "".to_string()).unwrap();
+
+        context(PLATFORM_DAO);
+        contract.dissolve("solar".to_string()).unwrap();
+        context("founder.testnet");
+        assert_eq!(
+            contract.create_proposal("solar".to_string(), 0, "audit".to_string(), "".to_string()).unwrap_err(),
+            ContractError::DaoDissolved
+        );
+    }
+
+    #[test]
+    fn device_errors_have_stable_codes() {
+        let mut contract = contract_with_dao();
+        let metadata = DeviceMetadata {
+            name: "roof".to_string(),
+            location: "tunis".to_string(),
+            energy_source: "solar".to_string(),
+            capacity_kw: 10,
+        };
+        assert_eq!(contract.get_device(0).unwrap_err(), ContractError::NotFound("device"));
+        assert_eq!(contract.get_readings(0, 0, 10).unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(contract.get_number_readings(0).unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(contract.get_certificate("0".to_string()).unwrap_err().code(), "NOT_FOUND");
+
+        context("outsider.testnet");
+        let error = contract.register_device("solar".to_string(), metadata.clone()).unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("dao members"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+
+        context("founder.testnet");
+        let id = contract.register_device("solar".to_string(), metadata).unwrap();
+        assert_eq!(
+            contract.submit_reading(id, 100, 0).unwrap_err(),
+            ContractError::InvalidState("device is not approved yet")
+        );
+        assert_eq!(contract.get_dao_devices("solar".to_string(), 0, 10).unwrap().len(), 1);
+        assert_eq!(contract.get_number_readings(id), Ok(0));
+    }
 }
@@ -1,10 +1,13 @@
 use near_sdk::{ext_contract};
 use serde::{Serialize, Deserialize};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env, near_bindgen, Gas};
+use near_sdk::{env, near_bindgen, Gas, AccountId};
 use near_sdk::collections::{Vector, UnorderedMap};
+use lightency_errors::{require, ContractError};
 
 pub const TGAS: u64 = 1_000_000_000_000;
+// Time to wait after an unstake before withdrawing, in nanoseconds
+pub const UNSTAKE_DELAY: u64 = 180000000;
 
 #[derive(BorshDeserialize, BorshSerialize)]
 #[derive(Serialize,Deserialize)]
@@ -33,6 +36,10 @@ pub trait Treasury {
 pub struct Rewardercontract {
     redeemers:Vector<String>,
     staker_data:UnorderedMap<String,Data>,
+    // Linked contracts, set at initialization
+    lts_token: AccountId,
+    staking: AccountId,
+    treasury: AccountId,
 }
 
 impl Default for Rewardercontract {
@@ -41,12 +48,22 @@ impl Default for Rewardercontract {
     }
 }
 
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
+}
+
+impl Rewardercontract {
+    // Make sure that the caller of the function is the staking contract,
+    // which passes the staker it acts for
+    fn assert_staking(&self) -> Result<(), ContractError> {
+        require(
+            env::predecessor_account_id() == self.staking,
+            ContractError::Unauthorized("staking contract"),
+        )
+    }
 }
 
 // Implement the Rewardercontract structure
@@ -54,25 +71,31 @@ fn assert_self() {
 impl Rewardercontract {
 
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(lts_token: AccountId, staking: AccountId, treasury: AccountId) -> Self {
         Self {
             redeemers: Vector::new(b"a"),
             staker_data: UnorderedMap::new(b"m"),
+            lts_token,
+            staking,
+            treasury,
         }
     }
 
     // delete all stakers
-    pub fn delete_all_stakers(&mut self) {
-        assert_self();
+    #[handle_result]
+    pub fn delete_all_stakers(&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         self.staker_data.clear();
+        Ok(())
     }
 
     pub fn redeem(&mut self,account:String){
         self.redeemers.push(&account);
     }
 
-    pub fn add_staker(&mut self, account:String, amount:u128) {
+    #[handle_result]
+    pub fn add_staker(&mut self, account:String, amount:u128) -> Result<(), ContractError> {
+        self.assert_staking()?;
         if self.staker_data.get(&account).is_none() {
             let data = Data {
                 amount : amount,
@@ -83,7 +106,7 @@ impl Rewardercontract {
                 unstake_timestamp:0
             };
             self.staker_data.insert(&account, &data);
-            let account_treasury= "treasurydao.testnet".to_string().try_into().unwrap();
+            let account_treasury= self.treasury.clone();
             ext_treasury::ext(account_treasury)
                 .with_static_gas(Gas(2 * TGAS))
                 .add_staker(account.clone());
@@ -93,11 +116,12 @@ impl Rewardercontract {
             data.time = env::block_timestamp(); 
             self.staker_data.insert(&account, &data);
             self.staker_data.insert(&account, &data);
-            let account_treasury= "treasurydao.testnet".to_string().try_into().unwrap();
+            let account_treasury= self.treasury.clone();
             ext_treasury::ext(account_treasury)
                 .with_static_gas(Gas(2 * TGAS))
                 .add_staker(account.clone());
         }
+        Ok(())
     }
 
     pub fn check_staker(&self, account:String) -> bool {
@@ -120,97 +144,135 @@ impl Rewardercontract {
         sum
     }
 
-    pub fn get_data(&self, account:String) -> Data {
-        self.staker_data.get(&account).unwrap()
+    #[handle_result]
+    pub fn get_data(&self, account:String) -> Result<Data, ContractError> {
+        self.staker_data.get(&account).ok_or(ContractError::NotFound("staker"))
     } 
 
-    pub fn unstake(&mut self, account:String, amount:u128){
-        if self.check_staker(account.clone()){
-            if amount < self.get_data(account.clone()).amount {
-                let mut data=self.get_data(account.clone());
-                data.amount-=amount;
-                data.unstaked_amount+=amount;
-                data.unstake_timestamp=env::block_timestamp();
-                self.staker_data.insert(&account.clone(), &data);
-            }else if amount == self.get_data(account.clone()).amount {
-                let mut data=self.get_data(account.clone());
-                data.amount-=amount;
-                data.unstaked_amount+=amount;
-                data.unstake_timestamp=env::block_timestamp();
-                self.staker_data.insert(&account.clone(), &data);
-            }else{
-                panic!("You don't have enough staked amount !!!");
-            }
-        }else {
-            panic!("You are not one of the stakers");
-        }
+    #[handle_result]
+    pub fn unstake(&mut self, account:String, amount:u128) -> Result<(), ContractError> {
+        self.assert_staking()?;
+        let mut data=self.get_data(account.clone())?;
+        require(amount <= data.amount, ContractError::InsufficientBalance)?;
+        data.amount-=amount;
+        data.unstaked_amount+=amount;
+        data.unstake_timestamp=env::block_timestamp();
+        self.staker_data.insert(&account, &data);
+        Ok(())
     }
 
-    pub fn withdraw(&mut self, account:String, amount:u128){
-        if self.check_staker(account.clone()){
-            if env::block_timestamp() > self.get_data(account.clone()).unstake_timestamp + 180000000 {
-                if amount > self.get_data(account.clone()).unstaked_amount {
-                    panic!("You don't have enough unstaked amount !!!");
-                }else {
-                    let mut data=self.get_data(account.clone());
-                    data.unstaked_amount-=amount;
-                    self.staker_data.insert(&account.clone(), &data);
-                }
-            }else {
-                panic!("You must wait 48 Hours after your last unstake");
-            }
-        }else {
-            panic!("You are not one of the unstakers");
-        }
+    #[handle_result]
+    pub fn withdraw(&mut self, account:String, amount:u128) -> Result<(), ContractError> {
+        self.assert_staking()?;
+        let mut data=self.get_data(account.clone())?;
+        require(env::block_timestamp() > data.unstake_timestamp + UNSTAKE_DELAY, ContractError::TooEarly)?;
+        require(amount <= data.unstaked_amount, ContractError::InsufficientBalance)?;
+        data.unstaked_amount-=amount;
+        self.staker_data.insert(&account, &data);
+        Ok(())
     }
 
-    pub fn withdraw_reward(&mut self,account:String){
-        if self.check_staker(account.clone()){
-            let mut data=self.get_data(account.clone());
-            let account_lts= "light-token.testnet".to_string().try_into().unwrap();
-            ext_lts::ext(account_lts)
-                .with_static_gas(Gas(2 * TGAS))
-                .with_attached_deposit(1)
-                .ft_transfer(account.clone(),((data.reward*100000000.0) as u128).to_string(),"".to_string());
-            data.reward=0.0;
-            self.staker_data.insert(&account.clone(), &data);
-        }else {
-            panic!("You are not one of the unstakers");
-        }
+    #[handle_result]
+    pub fn withdraw_reward(&mut self,account:String) -> Result<(), ContractError> {
+        let mut data=self.get_data(account.clone())?;
+        let account_lts= self.lts_token.clone();
+        ext_lts::ext(account_lts)
+            .with_static_gas(Gas(2 * TGAS))
+            .with_attached_deposit(1)
+            .ft_transfer(account.clone(),((data.reward*100000000.0) as u128).to_string(),"".to_string());
+        data.reward=0.0;
+        self.staker_data.insert(&account, &data);
+        Ok(())
     }
 
-    pub fn get_total_amount_per_wallet(&self, account:String) -> f64{
-        self.get_data(account.clone()).amount as f64+ self.get_data(account.clone()).reward
+    #[handle_result]
+    pub fn get_total_amount_per_wallet(&self, account:String) -> Result<f64, ContractError> {
+        let data=self.get_data(account)?;
+        Ok(data.amount as f64+ data.reward)
     }
 
     // pub fn get_balance(&self) -> u128 {
-    //     let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+    //     let account_lts= self.lts_token.clone();
     //     ext_lts::ext(account_lts)
     //             .with_static_gas(Gas(2 * TGAS))
     //             .ft_balance_of(env::current_account_id().to_string());
     // }
 
 
-    pub fn calculaterewards(&self,account:String)-> f64{
+    #[handle_result]
+    pub fn calculaterewards(&self,account:String)-> Result<f64, ContractError> {
         //Reward to stakers= Total staked (t) X APY(t) 
         //APY(t)=Staking pool supply/total staked(t) X Yield parameter.
-        let staked_per_wallet = self.get_total_amount_per_wallet(account);
+        let staked_per_wallet = self.get_total_amount_per_wallet(account)?;
         let reward_pool = 100 as f64;
         let total_reward = (reward_pool / 1095 as f64) as f64;
         let apy=(total_reward / self.get_totalstaked() as f64) as f64;
         let reward = (apy * staked_per_wallet as f64) as f64;
-        return reward;
+        return Ok(reward);
     } 
 
-    pub fn update_reward(&mut self,account:String){
-        let mut new_data= self.get_data(account.clone());
-        if env::block_timestamp() > new_data.next_reward_time {
-            let add_reward= self.calculaterewards(account.clone());
-            new_data.reward = new_data.reward + add_reward;
-            new_data.next_reward_time = new_data.next_reward_time + 120000000;
-            self.staker_data.insert(&account, &new_data);
-        }else {
-            panic!("You have not earned reward yet");
-        }
+    #[handle_result]
+    pub fn update_reward(&mut self,account:String) -> Result<(), ContractError> {
+        let mut new_data= self.get_data(account.clone())?;
+        require(env::block_timestamp() > new_data.next_reward_time, ContractError::TooEarly)?;
+        let add_reward= self.calculaterewards(account.clone())?;
+        new_data.reward = new_data.reward + add_reward;
+        new_data.next_reward_time = new_data.next_reward_time + 120000000;
+        self.staker_data.insert(&account, &new_data);
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn context(predecessor: &str, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("rewarder_contract.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .block_timestamp(timestamp)
+            .build());
+    }
+
+    fn contract() -> Rewardercontract {
+        Rewardercontract::new(
+            "light-token.testnet".parse().unwrap(),
+            "staking_contract.testnet".parse().unwrap(),
+            "treasurydao.testnet".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn unknown_staker_is_not_found() {
+        context("staking_contract.testnet", 0);
+        let mut contract = contract();
+        assert_eq!(contract.get_data("alice.testnet".to_string()).err(), Some(ContractError::NotFound("staker")));
+        assert_eq!(contract.unstake("alice.testnet".to_string(), 1).err(), Some(ContractError::NotFound("staker")));
+    }
+
+    #[test]
+    fn withdraw_waits_for_the_unstake_delay() {
+        context("staking_contract.testnet", 0);
+        let mut contract = contract();
+        contract.add_staker("alice.testnet".to_string(), 10).unwrap();
+        contract.unstake("alice.testnet".to_string(), 4).unwrap();
+        assert_eq!(contract.withdraw("alice.testnet".to_string(), 4).err(), Some(ContractError::TooEarly));
+
+        context("staking_contract.testnet", UNSTAKE_DELAY + 1);
+        assert_eq!(contract.withdraw("alice.testnet".to_string(), 5).err(), Some(ContractError::InsufficientBalance));
+        contract.withdraw("alice.testnet".to_string(), 4).unwrap();
+    }
+
+    #[test]
+    fn only_the_staking_contract_adds_stakers() {
+        context("alice.testnet", 0);
+        let mut contract = contract();
+        assert_eq!(
+            contract.add_staker("alice.testnet".to_string(), 10).err(),
+            Some(ContractError::Unauthorized("staking contract"))
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::{ext_contract, Promise, PromiseError,AccountId};
 use near_sdk::{env, near_bindgen, Gas};
+use lightency_errors::{require, ContractError};
 
 pub const TGAS: u64 = 1_000_000_000_000;
 
@@ -28,7 +29,10 @@ pub trait Lts {
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct StakingContract {
-
+    // Linked contracts, set at initialization
+    lts_token: AccountId,
+    pool: AccountId,
+    rewarder: AccountId,
 }
 
 // Define the default, which automatically initializes the contract
@@ -43,21 +47,22 @@ impl Default for StakingContract {
 #[near_bindgen]
 impl StakingContract {
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(lts_token: AccountId, pool: AccountId, rewarder: AccountId) -> Self {
         Self {
-
+            lts_token,
+            pool,
+            rewarder,
         }
     }
 
     // Methods.
 
     pub fn transfer (&mut self,amount: u128)-> Promise{
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+        let account_lts= self.lts_token.clone();
 
         let promise = ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
-        .burn_token(env::signer_account_id(),amount*100000000);
+        .burn_token(env::predecessor_account_id(),amount*100000000);
 
         return promise.then( // Create a promise to callback staking_callback
             Self::ext(env::current_account_id())
@@ -67,55 +72,53 @@ impl StakingContract {
     }
 
     #[private] // Public - but only callable by env::current_account_id()
-    pub fn transfer_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError> ,amount: u128) -> Promise {
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+    #[handle_result]
+    pub fn transfer_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError> ,amount: u128) -> Result<Promise, ContractError> {
+        let account_lts= self.lts_token.clone();
         // Check if the promise succeeded
-        if call_result.is_err() {
-        panic!("There was an error in the transfer process");
-        }
+        require(call_result.is_ok(), ContractError::CallFailed("LTS token contract"))?;
 
         // Return the promise
         let p = ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
         .mint_token(env::current_account_id(), amount*100000000);
-        p
+        Ok(p)
     }
 
     // stake function 
     pub fn stake(&self, amount: u128) -> Promise {
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+        let account_lts= self.lts_token.clone();
         // Create a promise to call tranfer LTS function
         let promise = ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
         .with_attached_deposit(1)
-        .ft_transfer("lightencypool.testnet".to_string(),(amount*100000000).to_string(),"".to_string());
+        .ft_transfer(self.pool.to_string(),(amount*100000000).to_string(),"".to_string());
         
         return promise.then( // Create a promise to callback staking_callback
         Self::ext(env::current_account_id())
         .with_static_gas(Gas(12 * TGAS))
-        .staking_callback(env::signer_account_id().to_string(),amount)
+        .staking_callback(env::predecessor_account_id().to_string(),amount)
         )
     }
 
     #[private] // Public - but only callable by env::current_account_id()
-    pub fn staking_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Promise {
-        let account_reward = "rewarder_contract.testnet".to_string().try_into().unwrap();
+    #[handle_result]
+    pub fn staking_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Result<Promise, ContractError> {
+        let account_reward = self.rewarder.clone();
         // Check if the promise succeeded
-        if call_result.is_err() {
-        panic!("There was an error contacting the pool contract");
-        }
+        require(call_result.is_ok(), ContractError::CallFailed("LTS token contract"))?;
 
         // Return the promise
         let p = ext_ft::ext(account_reward)
         .with_static_gas(Gas(5 * TGAS))
         .add_staker(account, amount);
-        p
+        Ok(p)
     }
 
 
     // unstake function 
     pub fn unstake(&self, amount: u128) -> Promise {
-        let account_pool= "lightencypool.testnet".to_string().try_into().unwrap();
+        let account_pool= self.pool.clone();
         // Create a promise to call tranfer LTS function
         let promise = ext_pool::ext(account_pool)
         .with_static_gas(Gas(7 * TGAS))
@@ -124,56 +127,95 @@ impl StakingContract {
         return promise.then( // Create a promise to callback unstaking_callback
         Self::ext(env::current_account_id())
         .with_static_gas(Gas(10 * TGAS))
-        .unstaking_callback(env::signer_account_id().to_string(),amount)
+        .unstaking_callback(env::predecessor_account_id().to_string(),amount)
         )
     }
     
     #[private] // Public - but only callable by env::current_account_id()
-    pub fn unstaking_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Promise {
-        let account_reward = "rewarder_contract.testnet".to_string().try_into().unwrap();
+    #[handle_result]
+    pub fn unstaking_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Result<Promise, ContractError> {
+        let account_reward = self.rewarder.clone();
         // Check if the promise succeeded
-        if call_result.is_err() {
-        panic!("There was an error contacting the pool contract");
-        }
+        require(call_result.is_ok(), ContractError::CallFailed("staking pool"))?;
     
         // Return the promise
         let p = ext_ft::ext(account_reward)
         .with_static_gas(Gas(2 * TGAS))
         .unstake(account,amount);
-        p
+        Ok(p)
     }
 
 
     // withdraw function 
     pub fn withdraw(&self, amount: u128) -> Promise {
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+        let account_lts= self.lts_token.clone();
         // Create a promise to call tranfer LTS function
         let promise = ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
         .with_attached_deposit(1)
-        .ft_transfer(env::signer_account_id().to_string(),(amount*100000000).to_string(),"".to_string());
+        .ft_transfer(env::predecessor_account_id().to_string(),(amount*100000000).to_string(),"".to_string());
             
         return promise.then( // Create a promise to callback withdraw_callback
         Self::ext(env::current_account_id())
         .with_static_gas(Gas(10 * TGAS))
-        .withdraw_callback(env::signer_account_id().to_string(),amount)
+        .withdraw_callback(env::predecessor_account_id().to_string(),amount)
         )
     }
     
     #[private] // Public - but only callable by env::current_account_id()
-    pub fn withdraw_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Promise {
-        let account_reward = "rewarder_contract.testnet".to_string().try_into().unwrap();
+    #[handle_result]
+    pub fn withdraw_callback(&mut self, #[callback_result] call_result: Result<(), PromiseError>, account:String ,amount: u128) -> Result<Promise, ContractError> {
+        let account_reward = self.rewarder.clone();
         // Check if the promise succeeded
-        if call_result.is_err() {
-        panic!("There was an error contacting the pool contract");
-        }
+        require(call_result.is_ok(), ContractError::CallFailed("LTS token contract"))?;
     
         // Return the promise
         let p = ext_ft::ext(account_reward)
             .with_static_gas(Gas(5 * TGAS))
             .withdraw(account,amount);
-        p
+        Ok(p)
+    }
+
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn contract() -> StakingContract {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("staking_contract.testnet".parse().unwrap())
+            .predecessor_account_id("staking_contract.testnet".parse().unwrap())
+            .build());
+        StakingContract::new(
+            "light-token.testnet".parse().unwrap(),
+            "lightencypool.testnet".parse().unwrap(),
+            "rewarder_contract.testnet".parse().unwrap(),
+        )
     }
 
+    #[test]
+    fn failed_calls_stop_the_staking_flows() {
+        let mut contract = contract();
+        let failed = ContractError::CallFailed("LTS token contract");
+        assert_eq!(contract.transfer_callback(Err(PromiseError::Failed), 5).err(), Some(failed));
+        assert_eq!(contract.staking_callback(Err(PromiseError::Failed), "alice.testnet".to_string(), 5).err(), Some(failed));
+        assert_eq!(contract.withdraw_callback(Err(PromiseError::Failed), "alice.testnet".to_string(), 5).err(), Some(failed));
+        assert_eq!(
+            contract.unstaking_callback(Err(PromiseError::Failed), "alice.testnet".to_string(), 5).err(),
+            Some(ContractError::CallFailed("staking pool"))
+        );
+    }
 
+    #[test]
+    fn successful_calls_reach_the_rewarder() {
+        let mut contract = contract();
+        assert!(contract.staking_callback(Ok(()), "alice.testnet".to_string(), 5).is_ok());
+        assert!(contract.unstaking_callback(Ok(()), "alice.testnet".to_string(), 5).is_ok());
+        assert!(contract.withdraw_callback(Ok(()), "alice.testnet".to_string(), 5).is_ok());
+        assert!(contract.transfer_callback(Ok(()), 5).is_ok());
+    }
 }
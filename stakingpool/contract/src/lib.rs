@@ -1,6 +1,7 @@
 use near_sdk::{ext_contract};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env,Gas, near_bindgen};
+use near_sdk::{env,Gas, near_bindgen, AccountId};
+use lightency_errors::{require, ContractError};
 
 pub const TGAS: u64 = 1_000_000_000_000;
 
@@ -13,7 +14,9 @@ pub trait Lts {
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct StakingPoolContract {
-
+    // Linked contracts, set at initialization
+    lts_token: AccountId,
+    staking: AccountId,
 }
 
 impl Default for StakingPoolContract {
@@ -28,19 +31,49 @@ impl Default for StakingPoolContract {
 impl StakingPoolContract {
 
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(lts_token: AccountId, staking: AccountId) -> Self {
         Self {
+            lts_token,
+            staking,
         }
     }
     
-    pub fn transfer_lts (&mut self, amount:u128){
-        let account_lts= "light-token.testnet".to_string().try_into().unwrap();
+    // Release unstaked LTS to the staking contract, only it can ask for it
+    #[handle_result]
+    pub fn transfer_lts (&mut self, amount:u128) -> Result<(), ContractError>{
+        require(
+            env::predecessor_account_id() == self.staking,
+            ContractError::Unauthorized("staking contract"),
+        )?;
+        let account_lts= self.lts_token.clone();
         // transfer lts to the singner 
         ext_lts::ext(account_lts)
         .with_static_gas(Gas(2 * TGAS))
         .with_attached_deposit(1)
-        .ft_transfer("staking_contract.testnet".to_string(),(amount*100000000).to_string(),"".to_string());
+        .ft_transfer(self.staking.to_string(),(amount*100000000).to_string(),"".to_string());
+        Ok(())
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn only_the_staking_contract_releases_lts() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("lightencypool.testnet".parse().unwrap())
+            .predecessor_account_id("alice.testnet".parse().unwrap())
+            .build());
+        let mut contract = StakingPoolContract::new(
+            "light-token.testnet".parse().unwrap(),
+            "staking_contract.testnet".parse().unwrap(),
+        );
+        let error = contract.transfer_lts(10).unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("staking contract"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+    }
+}
@@ -3,6 +3,7 @@ use near_sdk::collections::Vector;
 use near_sdk::ext_contract;
 use near_sdk::{env, near_bindgen, Gas, AccountId};
 use serde::{Serialize,Deserialize};
+use lightency_errors::{require, ContractError};
 
 #[ext_contract(ext_ft)]
 pub trait lighttoken {
@@ -47,6 +48,8 @@ impl Vestors {
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct VestingContract {
     records: Vector<Vestors>,
+    // LTS token contract, set at initialization
+    lts_token: AccountId,
 }
 
 // Define the default, which automatically initializes the contract
@@ -57,12 +60,11 @@ impl Default for VestingContract {
 }
 
 // Make sure that the caller of the function is the owner
-fn assert_self() {
-    assert_eq!(
-        env::current_account_id(),
-        env::predecessor_account_id(),
-        "Can only be called by owner"
-    );
+fn assert_self() -> Result<(), ContractError> {
+    require(
+        env::current_account_id() == env::predecessor_account_id(),
+        ContractError::Unauthorized("contract owner"),
+    )
 }
 
 // Implement the contract structure
@@ -70,26 +72,28 @@ fn assert_self() {
 #[near_bindgen]
 impl VestingContract {
     #[init]
-    pub fn new() -> Self {
-        assert!(env::state_read::<Self>().is_none(), "Already initialized");
+    pub fn new(lts_token: AccountId) -> Self {
         Self {
             records: Vector::new(b"a"),
+            lts_token,
         }
     }
 
     // Owner's methods. Can only be called by the owner
-    pub fn delete_all(&mut self){
-        assert_self();
+    #[handle_result]
+    pub fn delete_all(&mut self) -> Result<(), ContractError> {
+        assert_self()?;
         for _i in 0..self.records.len(){
             self.records.pop();
         }
+        Ok(())
     }
 
     // Function that delete a specific vestor 
     pub fn delete_a_vestor (&mut self,name:String){
         if name=="".to_string() {
             for i in 0..self.records.len() {
-                if self.records.get(i).unwrap().owner_id == env::signer_account_id().to_string() {
+                if self.records.get(i).unwrap().owner_id == env::predecessor_account_id().to_string() {
                     self.records.swap_remove(i);
                 }
             }
@@ -116,14 +120,12 @@ impl VestingContract {
     // Function to get id
 
     // Function to get a specific vestor
-    pub fn get_vestor(&self, id:&String) -> Vestors {
-        let mut vestor = Vestors::new();
-        for i in 0..self.records.len() {
-            if &self.records.get(i).unwrap().id == id {
-                vestor = self.records.get(i).unwrap();
-            }
-        }
-        vestor
+    #[handle_result]
+    pub fn get_vestor(&self, id:&String) -> Result<Vestors, ContractError> {
+        self.records
+            .iter()
+            .find(|vestor| &vestor.id == id)
+            .ok_or(ContractError::NotFound("vestor"))
     }
 
     // Function to get all locked amount of all accounts
@@ -153,9 +155,10 @@ impl VestingContract {
         id: String,
         amount_of_token: u128,
     ) {
+        let owner = env::predecessor_account_id();
         let vestor = Vestors {
             id:id,
-            owner_id: env::signer_account_id().to_string(),
+            owner_id: owner.to_string(),
             amount_of_token: amount_of_token,
             locked_amount: 3 * (amount_of_token/4),
             unlocked_amount: amount_of_token/4,
@@ -164,54 +167,57 @@ impl VestingContract {
             nb_time_payment: 1,
         };
         self.records.push(&vestor);
-        self.add_storage_deposit();
-        self.mint_lts(amount_of_token/4);
+        self.add_storage_deposit(owner.clone());
+        self.mint_lts(owner, amount_of_token/4);
     }
 
-    pub fn refresh (&mut self,v_id: String) {
-        if self.get_vestor(&v_id).nb_time_payment == 1 && env::block_timestamp_ms() > self.get_vestor(&v_id).timestamp + (2 * 60000) {
-            self.change_data(&self.get_vestor(&v_id).id);
-            self.mint_lts(self.get_vestor(&v_id).amount_of_token/4);
+    #[handle_result]
+    pub fn refresh (&mut self,v_id: String) -> Result<(), ContractError> {
+        if self.get_vestor(&v_id)?.nb_time_payment == 1 && env::block_timestamp_ms() > self.get_vestor(&v_id)?.timestamp + (2 * 60000) {
+            self.change_data(&self.get_vestor(&v_id)?.id)?;
+            self.mint_lts(self.get_vestor(&v_id)?.owner_id.try_into().unwrap(), self.get_vestor(&v_id)?.amount_of_token/4);
             env::log_str("second payment done");
         }
         env::log_str("hihihihihi");
-        if self.get_vestor(&v_id).nb_time_payment == 2 && env::block_timestamp_ms() > self.get_vestor(&v_id).timestamp + (4 * 60000) {
-            self.change_data(&self.get_vestor(&v_id).id);
-            self.mint_lts(self.get_vestor(&v_id).amount_of_token/4);
+        if self.get_vestor(&v_id)?.nb_time_payment == 2 && env::block_timestamp_ms() > self.get_vestor(&v_id)?.timestamp + (4 * 60000) {
+            self.change_data(&self.get_vestor(&v_id)?.id)?;
+            self.mint_lts(self.get_vestor(&v_id)?.owner_id.try_into().unwrap(), self.get_vestor(&v_id)?.amount_of_token/4);
             env::log_str("third payment done");
         }
         env::log_str("hihihihi2222");
-        if self.get_vestor(&v_id).nb_time_payment == 3 && env::block_timestamp_ms() > self.get_vestor(&v_id).timestamp + (6 * 60000) {
-            self.change_data(&self.get_vestor(&v_id).id);
-            self.mint_lts(self.get_vestor(&v_id).amount_of_token/4);
+        if self.get_vestor(&v_id)?.nb_time_payment == 3 && env::block_timestamp_ms() > self.get_vestor(&v_id)?.timestamp + (6 * 60000) {
+            self.change_data(&self.get_vestor(&v_id)?.id)?;
+            self.mint_lts(self.get_vestor(&v_id)?.owner_id.try_into().unwrap(), self.get_vestor(&v_id)?.amount_of_token/4);
             env::log_str("fourth payment done");
         }
-        if self.get_vestor(&v_id).nb_time_payment == 4 { 
+        if self.get_vestor(&v_id)?.nb_time_payment == 4 { 
             env::log_str("already paid");
         }
-        
+        Ok(())
     }
 
 
     /****** BACKUP FUNCTIONS ******/
 
     // Function to mint LTS 
-    pub fn mint_lts (&mut self, amount:u128) {
-        let contract_account = "light-token.testnet".to_string().try_into().unwrap();
+    #[private]
+    pub fn mint_lts (&mut self, account: AccountId, amount:u128) {
+        let contract_account = self.lts_token.clone();
 
         ext_ft::ext(contract_account)
             .with_static_gas(Gas(5_000_000_000_000))
-            .mint_token(env::signer_account_id(), amount*100000000);
+            .mint_token(account, amount*100000000);
     }
 
     // Function to add the vestor in the storage of the LTS token
-    pub fn add_storage_deposit (&mut self) {
-        let contract_account = "light-token.testnet".to_string().try_into().unwrap();
+    #[private]
+    pub fn add_storage_deposit (&mut self, account: AccountId) {
+        let contract_account = self.lts_token.clone();
 
         ext_ft::ext(contract_account)
             .with_attached_deposit(1000000000000000000000000)
             .with_static_gas(Gas(5_000_000_000_000))
-            .storage_deposit(env::signer_account_id().to_string());
+            .storage_deposit(account.to_string());
     }
 
 
@@ -226,11 +232,12 @@ impl VestingContract {
     }
 
     // Function to change data 
-    pub fn change_data (&mut self, v_id: &String){
-        let current_vestor = self.get_vestor(&v_id);
+    #[handle_result]
+    pub fn change_data (&mut self, v_id: &String) -> Result<(), ContractError> {
+        let current_vestor = self.get_vestor(&v_id)?;
         let vestor = Vestors {
             id: current_vestor.id,
-            owner_id: env::signer_account_id().to_string(),
+            owner_id: current_vestor.owner_id,
             amount_of_token: current_vestor.amount_of_token,
             locked_amount: current_vestor.locked_amount - current_vestor.amount_of_token/4,
             unlocked_amount: current_vestor.unlocked_amount + current_vestor.amount_of_token/4,
@@ -239,6 +246,28 @@ impl VestingContract {
             nb_time_payment: current_vestor.nb_time_payment + 1,
         };
         self.replace_vestor(vestor);
+        Ok(())
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn invalid_calls_return_stable_errors() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("vesting.testnet".parse().unwrap())
+            .predecessor_account_id("alice.testnet".parse().unwrap())
+            .build());
+        let mut contract = VestingContract::new("light-token.testnet".parse().unwrap());
+        let error = contract.delete_all().unwrap_err();
+        assert_eq!(error, ContractError::Unauthorized("contract owner"));
+        assert_eq!(error.code(), "UNAUTHORIZED");
+        assert_eq!(contract.get_vestor(&"alice".to_string()).unwrap_err(), ContractError::NotFound("vestor"));
+        assert_eq!(contract.refresh("alice".to_string()).unwrap_err().code(), "NOT_FOUND");
+    }
+}